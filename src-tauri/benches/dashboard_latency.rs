@@ -0,0 +1,121 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use cadence_lib::bench_support::{
+    get_habit_metrics, parse_journal_file, write_file_updates, Aggregation, DbPool, IngestConfig,
+    MetricDefinition, MetricType, MAX_BATCH_SIZE,
+};
+use chrono::{Days, NaiveDate};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const NOTE_COUNT: u64 = 365;
+const METRICS: [&str; 3] = ["pages_read", "exercise", "water_glasses"];
+
+fn ingest_config() -> IngestConfig {
+    IngestConfig {
+        tracked_metrics: METRICS
+            .iter()
+            .map(|name| MetricDefinition {
+                name: name.to_string(),
+                metric_type: MetricType::Integer,
+                aggregation: Aggregation::Sum,
+                unit: None,
+                aliases: Vec::new(),
+                checkbox_label: None,
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Writes a year of daily notes, each with a value for every metric.
+fn write_notes(dir: &Path, round: u64) -> Vec<String> {
+    let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+    (0..NOTE_COUNT)
+        .map(|day| {
+            let date = start.checked_add_days(Days::new(day)).unwrap();
+            let path = dir.join(format!("{}.md", date.format("%Y-%m-%d")));
+            let front_matter: String = METRICS
+                .iter()
+                .enumerate()
+                .map(|(i, name)| format!("{}: {}\n", name, (day + round + i as u64) % 40 + 1))
+                .collect();
+            fs::write(&path, format!("---\n{}---\n", front_matter)).unwrap();
+            path.to_string_lossy().to_string()
+        })
+        .collect()
+}
+
+fn setup(dir: &Path) -> (Arc<DbPool>, IngestConfig) {
+    let db = DbPool::open(dir.join("cadence.db").to_str().unwrap()).unwrap();
+    {
+        let conn = db.write().unwrap();
+        for name in METRICS {
+            conn.execute("INSERT INTO tracked_metrics (value) VALUES (?1)", [name])
+                .unwrap();
+        }
+    }
+    let config = ingest_config();
+    for batch in write_notes(dir, 0).chunks(MAX_BATCH_SIZE) {
+        ingest(batch, &config, &db);
+    }
+    (Arc::new(db), config)
+}
+
+/// Parses the notes at `paths` and stores them in one transaction, as the sync
+/// worker's writer does with each batch.
+fn ingest(paths: &[String], config: &IngestConfig, db: &DbPool) {
+    let updates = paths
+        .iter()
+        .filter_map(|path| parse_journal_file(path, config, db).unwrap())
+        .collect::<Vec<_>>();
+    write_file_updates(db, &updates).unwrap();
+}
+
+fn dashboard_latency(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let (db, config) = setup(dir.path());
+
+    c.bench_function("dashboard idle", |b| {
+        b.iter(|| get_habit_metrics(&db).unwrap())
+    });
+
+    // Rewrite and re-read every note for as long as the dashboard is being measured,
+    // in batches the way a resync after a settings change would.
+    let stop = Arc::new(AtomicBool::new(false));
+    let ingest = {
+        let db = Arc::clone(&db);
+        let stop = Arc::clone(&stop);
+        let notes = dir.path().join("notes");
+        fs::create_dir_all(&notes).unwrap();
+        thread::spawn(move || {
+            let mut round = 1;
+            while !stop.load(Ordering::Relaxed) {
+                for batch in write_notes(&notes, round).chunks(MAX_BATCH_SIZE) {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    ingest(batch, &config, &db);
+                }
+                round += 1;
+            }
+        })
+    };
+
+    c.bench_function("dashboard during bulk ingest", |b| {
+        b.iter(|| get_habit_metrics(&db).unwrap())
+    });
+
+    stop.store(true, Ordering::Relaxed);
+    ingest.join().unwrap();
+}
+
+criterion_group!(benches, dashboard_latency);
+criterion_main!(benches);
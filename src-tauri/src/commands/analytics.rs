@@ -3,7 +3,12 @@ use serde::Serialize;
 use tauri::State;
 
 use crate::{
-    core::read_journal::DB_DATE_FORMAT, db::streaks::compute_longest_streak, DbConnection,
+    core::{period::Granularity, read_journal::DB_DATE_FORMAT},
+    db::{
+        metrics::{get_period_metrics, PeriodMetric},
+        streaks::compute_longest_streak,
+    },
+    DbConnection,
 };
 
 #[derive(Serialize)]
@@ -40,7 +45,7 @@ pub struct DayActivity {
 
 #[tauri::command]
 pub fn get_weekly_activity(db: State<'_, DbConnection>) -> Result<Vec<DayActivity>, String> {
-    let conn = db.lock().unwrap();
+    let conn = db.read().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
             "
@@ -52,6 +57,7 @@ pub fn get_weekly_activity(db: State<'_, DbConnection>) -> Result<Vec<DayActivit
           inner join tracked_metrics tm on m.name = tm.value
         where
           m.value > 0
+          and m.period = 'day'
           and m.date between '2025-10-12' and '2025-10-18'
         group by
           m.date
@@ -72,11 +78,31 @@ pub fn get_weekly_activity(db: State<'_, DbConnection>) -> Result<Vec<DayActivit
     Ok(results)
 }
 
+/// Metric values of the weekly or monthly notes whose period starts between `from` and
+/// `to`. These are reported per period rather than spread over its days.
+#[tauri::command]
+pub fn get_periodic_metrics(
+    db: State<'_, DbConnection>,
+    granularity: &str,
+    from: &str,
+    to: &str,
+) -> Result<Vec<PeriodMetric>, String> {
+    let granularity = granularity
+        .parse::<Granularity>()
+        .map_err(|e| e.to_string())?;
+    let parse_date = |date: &str| {
+        NaiveDate::parse_from_str(date, DB_DATE_FORMAT)
+            .map_err(|e| format!("Invalid date {}: {}", date, e))
+    };
+    get_period_metrics(&db, granularity, None, parse_date(from)?, parse_date(to)?)
+        .map_err(|e| format!("Error getting periodic metrics: {}", e))
+}
+
 fn get_all_habits_longest_streak(db: &DbConnection) -> Result<i64, String> {
-    let conn = db.lock().unwrap();
+    let conn = db.read().map_err(|e| e.to_string())?;
 
     let mut stmt = conn
-        .prepare("SELECT DISTINCT date FROM metrics WHERE value > 0 ORDER BY date ASC")
+        .prepare("SELECT DISTINCT date FROM metrics WHERE value > 0 AND period = 'day' ORDER BY date ASC")
         .map_err(|e| format!("Database error preparing streak query: {}", e))?;
 
     let date_iter = stmt
@@ -90,10 +116,10 @@ fn get_all_habits_longest_streak(db: &DbConnection) -> Result<i64, String> {
 }
 
 fn count_all_habits(db: &DbConnection) -> Result<Option<u32>, String> {
-    let conn = db.lock().unwrap();
+    let conn = db.read().map_err(|e| e.to_string())?;
     let total = conn
         .query_one(
-            "select count(distinct name) from metrics where value > 0",
+            "select count(distinct name) from metrics where value > 0 and period = 'day'",
             [],
             |row| row.get::<_, Option<u32>>(0),
         )
@@ -102,13 +128,15 @@ fn count_all_habits(db: &DbConnection) -> Result<Option<u32>, String> {
 }
 
 fn get_completion_rate(db: &DbConnection) -> Result<i64, String> {
-    let conn = db.lock().unwrap();
+    let conn = db.read().map_err(|e| e.to_string())?;
 
     // 1. Get Date Range (MIN and MAX logged dates)
     let (min_date_str, max_date_str) = conn
-        .query_row("SELECT MIN(date), MAX(date) FROM metrics", [], |row| {
-            Ok((row.get::<_, String>(0), row.get::<_, String>(1)))
-        })
+        .query_row(
+            "SELECT MIN(date), MAX(date) FROM metrics WHERE period = 'day'",
+            [],
+            |row| Ok((row.get::<_, String>(0), row.get::<_, String>(1))),
+        )
         .map_err(|e| format!("Database error fetching date range: {}", e))?;
 
     if min_date_str.is_err() || max_date_str.is_err() {
@@ -127,15 +155,19 @@ fn get_completion_rate(db: &DbConnection) -> Result<i64, String> {
     let total_days_logged = (end_date - start_date).num_days() + 1;
 
     let total_successes = conn
-        .query_row("SELECT COUNT(*) FROM metrics WHERE value > 0", [], |row| {
-            row.get::<_, i64>(0)
-        })
+        .query_row(
+            "SELECT COUNT(*) FROM metrics WHERE value > 0 AND period = 'day'",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
         .map_err(|e| format!("Database error fetching total successes: {}", e))?;
 
     let total_unique_habits = conn
-        .query_row("SELECT COUNT(DISTINCT name) FROM metrics", [], |row| {
-            row.get::<_, i64>(0)
-        })
+        .query_row(
+            "SELECT COUNT(DISTINCT name) FROM metrics WHERE period = 'day'",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
         .map_err(|e| format!("Database error fetching total unique habits: {}", e))?;
 
     if total_days_logged <= 0 || total_unique_habits <= 0 {
@@ -150,12 +182,12 @@ fn get_completion_rate(db: &DbConnection) -> Result<i64, String> {
 }
 
 fn count_active_days(db: &DbConnection) -> Result<i64, String> {
-    let conn = db.lock().unwrap();
+    let conn = db.read().map_err(|e| e.to_string())?;
 
     // Use COUNT(DISTINCT date) to find the number of unique days with activity (value > 0).
     let total_active_days = conn
         .query_one(
-            "SELECT COUNT(DISTINCT date) FROM metrics WHERE value > 0",
+            "SELECT COUNT(DISTINCT date) FROM metrics WHERE value > 0 AND period = 'day'",
             [],
             |row| row.get::<_, i64>(0),
         )
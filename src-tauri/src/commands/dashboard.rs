@@ -6,17 +6,18 @@ use serde::Serialize;
 use tauri::State;
 
 use crate::{
-    core::read_journal::DB_DATE_FORMAT,
+    core::{metric_value::MetricType, period::Granularity, read_journal::DB_DATE_FORMAT},
     db::{
-        metrics,
+        metrics::{self, LoggedValue},
         streaks::{get_habit_streak, get_longest_habit_streak},
-        utils::get_all_habits,
+        utils::{get_all_habits, get_metric_type, get_metric_unit},
     },
     DbConnection,
 };
 
 #[derive(Serialize)]
 #[serde(rename_all = "lowercase")]
+#[allow(dead_code)]
 enum Trend {
     Stable,
     Up, //TODO: updae to use this two variants
@@ -29,10 +30,13 @@ pub struct DashboardMetrics {
     name: String,
     current_streak: i64,
     longest_streak: i64,
-    weekly_average: i32,
+    weekly_average: f64,
     display_name: String,
     last_updated: String,
-    monthly_total: u32,
+    monthly_total: f64,
+    metric_type: MetricType,
+    /// What the averages and totals are measured in, if the metric has a unit.
+    unit: Option<String>,
     trend: Trend,
 }
 
@@ -40,7 +44,7 @@ pub struct DashboardMetrics {
 #[serde(rename_all = "camelCase")]
 struct WeeklyMetricStat {
     date: String,
-    value: i32,
+    value: f64,
 }
 
 #[derive(Debug, Serialize)]
@@ -48,6 +52,9 @@ struct WeeklyMetricStat {
 pub struct WeeklyMetrics {
     prev_week: Vec<WeeklyMetricStat>,
     current_week: Vec<WeeklyMetricStat>,
+    /// Values set in the weekly notes of the two weeks, kept apart from the daily values.
+    prev_week_note: Option<f64>,
+    current_week_note: Option<f64>,
 }
 
 #[tauri::command]
@@ -74,6 +81,20 @@ pub fn get_weekly_metric_stats(
     Ok(result)
 }
 
+/// The individual values a habit was logged with on `date`, such as each run of a day
+/// with two runs.
+#[tauri::command]
+pub fn get_metric_events(
+    db: State<'_, DbConnection>,
+    habit_name: &str,
+    date: &str,
+) -> Result<Vec<LoggedValue>, String> {
+    let date = NaiveDate::parse_from_str(date, DB_DATE_FORMAT)
+        .map_err(|e| format!("Invalid date {}: {}", date, e))?;
+    metrics::get_logged_values(&db, habit_name, date)
+        .map_err(|e| format!("error getting metric events, {}", e))
+}
+
 fn weekly_metric_stats(
     db: &DbConnection,
     habit_name: &str,
@@ -109,9 +130,20 @@ fn weekly_metric_stats(
         (diff.num_days() + 1) as usize, // today included in the diff
     )?;
 
+    let week_note = |start: NaiveDate, end: NaiveDate| -> Result<Option<f64>, anyhow::Error> {
+        let notes =
+            metrics::get_period_metrics(db, Granularity::Week, Some(habit_name), start, end)?;
+        Ok(notes.first().map(|note| note.value))
+    };
+
     let result = WeeklyMetrics {
         prev_week: prev_week_data,
         current_week: current_week_data,
+        prev_week_note: week_note(prev_week.first_day(), prev_week.last_day())?,
+        current_week_note: week_note(
+            first_day_current_week,
+            first_day_current_week + Days::new(6),
+        )?,
     };
 
     Ok(result)
@@ -124,19 +156,20 @@ fn get_weekly_data(
     end: NaiveDate,
     num_of_days: usize,
 ) -> Result<Vec<WeeklyMetricStat>, anyhow::Error> {
-    let conn = db.lock().unwrap();
-    let mut stmt =
-        conn.prepare("SELECT date,value from metrics where name=?1 and date between ?2 and ?3")?;
+    let conn = db.read()?;
+    let mut stmt = conn.prepare(
+        "SELECT date,value from metrics where name=?1 and period='day' and date between ?2 and ?3",
+    )?;
     let rows = stmt.query_map(
         [
             habit_name,
             &start.format(DB_DATE_FORMAT).to_string(),
             &end.format(DB_DATE_FORMAT).to_string(),
         ],
-        |row| Ok((row.get::<_, String>(0), row.get::<_, i32>(1))),
+        |row| Ok((row.get::<_, String>(0), row.get::<_, f64>(1))),
     )?;
 
-    let mut data: HashMap<String, i32> = HashMap::with_capacity(7);
+    let mut data: HashMap<String, f64> = HashMap::with_capacity(7);
     for row in rows {
         let row = row.map_err(|e| {
             anyhow!(format!(
@@ -157,7 +190,7 @@ fn get_weekly_data(
     for date in start.iter_days().take(num_of_days) {
         let date_string = date.format(DB_DATE_FORMAT).to_string();
         // default to 0 if there was not a value from the db
-        let value = data.get(&date_string).unwrap_or(&0);
+        let value = data.get(&date_string).unwrap_or(&0.0);
         let entry = WeeklyMetricStat {
             date: date_string,
             value: *value,
@@ -168,7 +201,7 @@ fn get_weekly_data(
     Ok(result)
 }
 
-fn get_habit_metrics(db: &DbConnection) -> Result<Vec<DashboardMetrics>, String> {
+pub fn get_habit_metrics(db: &DbConnection) -> Result<Vec<DashboardMetrics>, String> {
     let habits = get_all_habits(db).map_err(|e| e.to_string())?;
     let mut metrics_list = Vec::with_capacity(habits.len());
     for habit in habits {
@@ -181,12 +214,15 @@ fn get_habit_metrics(db: &DbConnection) -> Result<Vec<DashboardMetrics>, String>
 fn get_summary_metric(db: &DbConnection, habit_name: &str) -> Result<DashboardMetrics, String> {
     let current_streak = get_habit_streak(db, habit_name).map_err(|e| e.to_string())?;
     let longest_streak = get_longest_habit_streak(db, habit_name).map_err(|e| e.to_string())?;
-    let weekly_avg = metrics::get_weekly_metric_avg(db, habit_name).map_err(|e| e.to_string())?;
-    let monthly_total =
-        metrics::get_monthly_metric_total(db, habit_name).map_err(|e| e.to_string())?;
+    let metric_type = get_metric_type(db, habit_name).map_err(|e| e.to_string())?;
+    let unit = get_metric_unit(db, habit_name).map_err(|e| e.to_string())?;
+    let weekly_avg =
+        metrics::get_weekly_metric_avg(db, habit_name, metric_type).map_err(|e| e.to_string())?;
+    let monthly_total = metrics::get_monthly_metric_total(db, habit_name, metric_type)
+        .map_err(|e| e.to_string())?;
 
     let display_name = habit_name.to_string(); //HACK: Placeholder for display name logic
-    let conn = db.lock().unwrap();
+    let conn = db.read().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare("SELECT updated_at FROM metrics WHERE name = ?1 ORDER BY updated_at DESC LIMIT 1")
         .map_err(|e| e.to_string())?;
@@ -204,28 +240,24 @@ fn get_summary_metric(db: &DbConnection, habit_name: &str) -> Result<DashboardMe
         longest_streak,
         weekly_average: weekly_avg,
         monthly_total,
+        metric_type,
+        unit,
         trend,
     })
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Mutex;
-
     use anyhow::Context;
 
     use super::*;
     // use crate::db::seed;
     use crate::db::metrics;
-    use crate::db::Db;
 
     fn init_db() -> Result<DbConnection, anyhow::Error> {
         let db_path = "/tmp/habitron.db";
-        let db = Db::new(db_path)
+        let db_conn = DbConnection::open(db_path)
             .with_context(|| "something went wrong initializing the database".to_string())?;
-
-        db.init_db().with_context(|| "Error seeding the database")?;
-        let db_conn = Mutex::new(db.into_connection());
         // seed::seed_development_data(&db_conn)?;
         Ok(db_conn)
     }
@@ -241,7 +273,8 @@ mod tests {
     #[test]
     fn get_weekly_average() -> Result<(), anyhow::Error> {
         let db_con = init_db()?;
-        let result = metrics::get_weekly_metric_avg(&db_con, "dsa_problems_solved")?;
+        let result =
+            metrics::get_weekly_metric_avg(&db_con, "dsa_problems_solved", MetricType::Integer)?;
         println!("result: {:#?}", result);
         Ok(())
     }
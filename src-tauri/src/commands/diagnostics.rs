@@ -0,0 +1,11 @@
+use tauri::State;
+
+use crate::db::diagnostics::{self, IngestDiagnostic};
+use crate::DbConnection;
+
+#[tauri::command]
+pub fn get_ingest_diagnostics(
+    db: State<'_, DbConnection>,
+) -> Result<Vec<IngestDiagnostic>, String> {
+    diagnostics::get_ingest_diagnostics(&db).map_err(|e| e.to_string())
+}
@@ -1,6 +1,8 @@
 pub mod analytics;
 pub mod dashboard;
+pub mod diagnostics;
 pub mod recent_activity;
 pub mod settings;
 pub mod streaks;
+pub mod sync;
 pub mod utils;
@@ -1,11 +1,47 @@
+use std::path::Path;
+
 use chrono::{Local, NaiveDate};
-use rusqlite::{fallible_iterator::FallibleIterator, Batch, Result};
+use rusqlite::{fallible_iterator::FallibleIterator, params, Batch, OptionalExtension, Result};
 use serde::Serialize;
 use tauri::AppHandle;
 
 use crate::{
-    core::{file_watcher::WatchCommand, read_journal::DB_DATE_TIME_FORMAT, resync_database},
-    db::utils::get_journal_files_path,
+    core::{
+        change_events::{emit_files_removed, emit_metrics_changed, MetricChanges},
+        checkboxes::CheckboxLabel,
+        derived::{in_dependency_order, DerivedMetric},
+        file_watcher::WatchCommand,
+        filename_pattern::FilenamePattern,
+        get_derived_metrics_from_db,
+        ingest_cache::IngestConfigCache,
+        inline_fields::FieldPrecedence,
+        metric_value::{Aggregation, MetricType},
+        parse_aggregation, parse_aliases,
+        path_filter::validate_globs,
+        read_journal::DB_DATE_TIME_FORMAT,
+        reconcile::purge_non_journal_files,
+        resync_database, resync_journal_roots,
+        source_format::SourceFormats,
+        units::Unit,
+    },
+    db::{
+        journal_roots::{
+            delete_journal_root, delete_root_file_rows, get_enabled_journal_roots,
+            get_enclosing_journal_roots, get_journal_root, get_journal_roots, upsert_journal_root,
+            JournalRoot,
+        },
+        metrics::{reaggregate_metric, recompute_derived_metrics, save_derived_metric},
+        utils::{
+            add_metric_dates, clear_file_meta, delete_file_rows, delete_file_rows_where,
+            delete_metric_rows, get_date_field, get_field_precedence, get_filename_pattern,
+            get_inline_fields, get_list_setting, get_metric_type, get_normalize_metric_keys,
+            get_prefer_date_field, get_reconcile_interval, get_source_formats, get_watch_debounce,
+            set_list_setting, set_setting, DATE_FIELD_SETTING, EXCLUDE_GLOBS_SETTING,
+            FIELD_PRECEDENCE_SETTING, FILENAME_PATTERN_SETTING, INCLUDE_GLOBS_SETTING,
+            INLINE_FIELDS_SETTING, NORMALIZE_METRIC_KEYS_SETTING, PREFER_DATE_FIELD_SETTING,
+            RECONCILE_INTERVAL_SETTING, SOURCE_FORMATS_SETTING, WATCH_DEBOUNCE_SETTING,
+        },
+    },
     DbConnection, WatcherState,
 };
 
@@ -13,6 +49,13 @@ use crate::{
 #[serde(rename_all = "camelCase")]
 pub struct TrackedMetric {
     name: String,
+    metric_type: MetricType,
+    aggregation: Aggregation,
+    aliases: Vec<String>,
+    checkbox_label: Option<String>,
+    /// Set for metrics computed from others rather than read from notes.
+    formula: Option<String>,
+    unit: Option<String>,
     active: bool,
     last_updated: String,
     entries: i32,
@@ -22,27 +65,68 @@ pub struct TrackedMetric {
 #[serde(rename_all = "camelCase")]
 pub struct Settings {
     tracked_metrics: Option<Vec<TrackedMetric>>,
+    /// The first enabled root, kept for the single-folder settings screen.
     journal_files_path: Option<String>,
+    journal_roots: Vec<JournalRoot>,
+    filename_pattern: String,
+    date_field: Option<String>,
+    prefer_date_field: bool,
+    include_globs: Vec<String>,
+    exclude_globs: Vec<String>,
+    inline_fields: bool,
+    field_precedence: FieldPrecedence,
+    normalize_metric_keys: bool,
+    source_formats: Vec<String>,
+    /// How long the watcher waits for a changed file to settle before reading it.
+    watch_debounce_ms: u64,
+    /// How often the journal is checked for changes the watcher missed; 0 when never.
+    reconcile_interval_minutes: u64,
 }
 
 #[tauri::command]
 pub fn get_settings(db: tauri::State<'_, DbConnection>) -> Result<Settings, String> {
     let tracked_metrics = get_tracked_metrics(&db).map_err(|e| e.to_string())?;
-    let journal_files_path = get_journal_files_path(&db).map_err(|e| e.to_string())?;
+    let journal_roots = get_journal_roots(&db).map_err(|e| e.to_string())?;
+    let journal_files_path = journal_roots
+        .iter()
+        .find(|root| root.enabled)
+        .map(|root| root.path.clone());
+    let filename_pattern = get_filename_pattern(&db).map_err(|e| e.to_string())?;
+    let date_field = get_date_field(&db).map_err(|e| e.to_string())?;
+    let prefer_date_field = get_prefer_date_field(&db).map_err(|e| e.to_string())?;
+    let include_globs = get_list_setting(&db, INCLUDE_GLOBS_SETTING).map_err(|e| e.to_string())?;
+    let exclude_globs = get_list_setting(&db, EXCLUDE_GLOBS_SETTING).map_err(|e| e.to_string())?;
+    let inline_fields = get_inline_fields(&db).map_err(|e| e.to_string())?;
+    let field_precedence = get_field_precedence(&db).map_err(|e| e.to_string())?;
+    let normalize_metric_keys = get_normalize_metric_keys(&db).map_err(|e| e.to_string())?;
+    let source_formats = get_source_formats(&db).map_err(|e| e.to_string())?;
+    let watch_debounce = get_watch_debounce(&db).map_err(|e| e.to_string())?;
+    let reconcile_interval = get_reconcile_interval(&db).map_err(|e| e.to_string())?;
 
     Ok(Settings {
         tracked_metrics,
         journal_files_path,
+        journal_roots,
+        filename_pattern: filename_pattern.to_string(),
+        date_field,
+        prefer_date_field,
+        include_globs,
+        exclude_globs,
+        inline_fields,
+        field_precedence,
+        normalize_metric_keys,
+        source_formats: source_formats.names(),
+        watch_debounce_ms: watch_debounce.as_millis() as u64,
+        reconcile_interval_minutes: reconcile_interval
+            .map_or(0, |interval| interval.as_secs() / 60),
     })
 }
 
 fn get_tracked_metrics(db: &DbConnection) -> Result<Option<Vec<TrackedMetric>>, anyhow::Error> {
-    let conn = db
-        .lock()
-        .map_err(|e| anyhow::anyhow!("Failed to lock connection: {}", e))?;
+    let conn = db.read()?;
 
     let mut stmt = conn.prepare(
-        "SELECT name, updated_at, COUNT(*) as entries 
+        "SELECT name, updated_at, COUNT(*) as entries, COALESCE(tracked_metrics.metric_type, 'integer'), tracked_metrics.aliases, tracked_metrics.checkbox_label, tracked_metrics.aggregation, tracked_metrics.formula, tracked_metrics.unit
          FROM metrics 
          left join tracked_metrics on tracked_metrics.value = metrics.name
          GROUP BY name 
@@ -54,11 +138,40 @@ fn get_tracked_metrics(db: &DbConnection) -> Result<Option<Vec<TrackedMetric>>,
             let name: String = row.get(0)?;
             let updated_at: String = row.get(1)?;
             let entries: i32 = row.get(2)?;
-
-            Ok((name, updated_at, entries))
+            let metric_type: String = row.get(3)?;
+            let aliases: Option<String> = row.get(4)?;
+            let checkbox_label: Option<String> = row.get(5)?;
+            let aggregation: Option<String> = row.get(6)?;
+            let formula: Option<String> = row.get(7)?;
+            let unit: Option<String> = row.get(8)?;
+
+            Ok((
+                name,
+                updated_at,
+                entries,
+                metric_type,
+                aliases,
+                checkbox_label,
+                aggregation,
+                formula,
+                unit,
+            ))
         })?
         .map(|res| {
-            let (name, updated_at, entries) = res?;
+            let (
+                name,
+                updated_at,
+                entries,
+                metric_type,
+                aliases,
+                checkbox_label,
+                aggregation,
+                formula,
+                unit,
+            ) = res?;
+            let metric_type = metric_type.parse::<MetricType>()?;
+            let aggregation = parse_aggregation(aggregation.as_deref(), metric_type)?;
+            let aliases = parse_aliases(aliases.as_deref())?;
             let last_updated = NaiveDate::parse_from_str(&updated_at, DB_DATE_TIME_FORMAT)
                 .map_err(|e| anyhow::anyhow!("Failed to parse date {}: {}", updated_at, e))?;
 
@@ -71,6 +184,12 @@ fn get_tracked_metrics(db: &DbConnection) -> Result<Option<Vec<TrackedMetric>>,
 
             Ok(TrackedMetric {
                 name,
+                metric_type,
+                aggregation,
+                aliases,
+                checkbox_label,
+                formula,
+                unit,
                 active,
                 last_updated: updated_at,
                 entries,
@@ -85,6 +204,7 @@ fn get_tracked_metrics(db: &DbConnection) -> Result<Option<Vec<TrackedMetric>>,
     }
 }
 
+/// Makes `path` the only journal root, for callers that predate multiple roots.
 #[tauri::command]
 pub async fn set_journal_files_path(
     db: tauri::State<'_, DbConnection>,
@@ -92,42 +212,176 @@ pub async fn set_journal_files_path(
     app: tauri::AppHandle,
     path: &str,
 ) -> Result<(), String> {
-    // Get the previous path before updating
-    let previous_path = get_journal_files_path(&db).ok().flatten();
+    let previous_roots = get_journal_roots(&db).map_err(|e| e.to_string())?;
 
-    {
-        let conn = db
-            .lock()
-            .map_err(|e| format!("Failed to lock database connection: {}", e))?;
+    for root in previous_roots.iter().filter(|root| root.path != path) {
+        if root.enabled {
+            send_watch_command(&watcher, WatchCommand::Unwatch(root.path.clone()))?;
+        }
+        delete_journal_root(&db, &root.path)
+            .map_err(|e| format!("Failed to remove journal root {}: {}", root.path, e))?;
+        let removed = delete_file_rows(&db, Path::new(&root.path)).map_err(|e| e.to_string())?;
+        emit_files_removed(&app, &removed);
+    }
 
-        let mut stmt = conn
-            .prepare("INSERT OR REPLACE INTO journals_files_path (value) VALUES (?1)")
-            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    let previous = previous_roots.into_iter().find(|root| root.path == path);
+    let was_enabled = previous.as_ref().is_some_and(|root| root.enabled);
+    let root = JournalRoot {
+        path: path.to_string(),
+        filename_pattern: previous.and_then(|root| root.filename_pattern),
+        enabled: true,
+    };
+    upsert_journal_root(&db, &root)
+        .map_err(|e| format!("Failed to set journal files path: {}", e))?;
 
-        stmt.execute([path])
-            .map_err(|e| format!("Failed to set journal files path: {}", e))?;
+    resync_journal_roots(app, vec![root])
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    if !was_enabled {
+        send_watch_command(&watcher, WatchCommand::Watch(path.to_string()))?;
+    }
+
+    println!("path: {:#?}", path);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_journal_roots(db: tauri::State<'_, DbConnection>) -> Result<Vec<JournalRoot>, String> {
+    get_journal_roots(&db).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn add_journal_root(
+    db: tauri::State<'_, DbConnection>,
+    watcher: tauri::State<'_, WatcherState>,
+    app: tauri::AppHandle,
+    path: String,
+    filename_pattern: Option<String>,
+) -> Result<(), String> {
+    if !Path::new(&path).is_dir() {
+        return Err(format!("{} is not a folder", path));
+    }
+    if get_journal_root(&db, &path)
+        .map_err(|e| e.to_string())?
+        .is_some()
+    {
+        return Err(format!("{} is already a journal root", path));
     }
 
-    resync_database(app, path)
+    let root = JournalRoot {
+        path,
+        filename_pattern: validate_root_pattern(filename_pattern)?,
+        enabled: true,
+    };
+    upsert_journal_root(&db, &root).map_err(|e| format!("Failed to add journal root: {}", e))?;
+    send_watch_command(&watcher, WatchCommand::Watch(root.path.clone()))?;
+
+    resync_journal_roots(app, vec![root])
         .await
         .map_err(|e| format!("Failed to resync database: {}", e))?;
 
-    // Unwatch previous path if it exists
-    if let Some(prev_path) = previous_path {
-        let sender_opt = {
-            let sender_guard = watcher
-                .lock()
-                .map_err(|e| format!("Failed to lock watcher sender: {}", e))?;
-            sender_guard.clone()
-        };
-        if let Some(sender) = sender_opt {
-            sender
-                .send(WatchCommand::Unwatch(prev_path))
-                .map_err(|e| format!("Failed to send unwatch command: {}", e))?;
+    Ok(())
+}
+
+/// Changes a root's filename pattern or toggles it. Disabling a root drops its metrics
+/// and stops watching it until it is enabled again.
+#[tauri::command]
+pub async fn update_journal_root(
+    db: tauri::State<'_, DbConnection>,
+    watcher: tauri::State<'_, WatcherState>,
+    configs: tauri::State<'_, IngestConfigCache>,
+    app: tauri::AppHandle,
+    path: String,
+    filename_pattern: Option<String>,
+    enabled: bool,
+) -> Result<(), String> {
+    let previous = get_journal_root(&db, &path)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("{} is not a journal root", path))?;
+
+    let root = JournalRoot {
+        path,
+        filename_pattern: validate_root_pattern(filename_pattern)?,
+        enabled,
+    };
+    upsert_journal_root(&db, &root).map_err(|e| format!("Failed to update journal root: {}", e))?;
+    configs.invalidate();
+
+    // Entry dates may resolve differently under a new pattern, so start the root over.
+    if !root.enabled || root.filename_pattern != previous.filename_pattern {
+        let removed = delete_root_file_rows(&db, &root.path).map_err(|e| e.to_string())?;
+        emit_files_removed(&app, &removed);
+    }
+
+    match (previous.enabled, root.enabled) {
+        (true, false) => {
+            send_watch_command(&watcher, WatchCommand::Unwatch(root.path.clone()))?;
+            let enclosing =
+                get_enclosing_journal_roots(&db, &root.path).map_err(|e| e.to_string())?;
+            resync_journal_roots(app, enclosing)
+                .await
+                .map_err(|e| format!("Failed to resync database: {}", e))?;
+        }
+        (false, true) => {
+            send_watch_command(&watcher, WatchCommand::Watch(root.path.clone()))?;
+            resync_journal_roots(app, vec![root])
+                .await
+                .map_err(|e| format!("Failed to resync database: {}", e))?;
+        }
+        (true, true) if root.filename_pattern != previous.filename_pattern => {
+            resync_journal_roots(app, vec![root])
+                .await
+                .map_err(|e| format!("Failed to resync database: {}", e))?;
         }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_journal_root(
+    db: tauri::State<'_, DbConnection>,
+    watcher: tauri::State<'_, WatcherState>,
+    configs: tauri::State<'_, IngestConfigCache>,
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<(), String> {
+    let root = get_journal_root(&db, &path)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("{} is not a journal root", path))?;
+
+    if root.enabled {
+        send_watch_command(&watcher, WatchCommand::Unwatch(root.path.clone()))?;
+    }
+    delete_journal_root(&db, &root.path)
+        .map_err(|e| format!("Failed to remove journal root: {}", e))?;
+    configs.invalidate();
+    let removed = delete_root_file_rows(&db, &root.path).map_err(|e| e.to_string())?;
+    emit_files_removed(&app, &removed);
+
+    let enclosing = get_enclosing_journal_roots(&db, &root.path).map_err(|e| e.to_string())?;
+    resync_journal_roots(app, enclosing)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    Ok(())
+}
+
+/// Checks a per-root filename pattern, treating a blank one as "use the global pattern".
+fn validate_root_pattern(pattern: Option<String>) -> Result<Option<String>, String> {
+    match pattern.as_deref().map(str::trim) {
+        Some(pattern) if !pattern.is_empty() => pattern
+            .parse::<FilenamePattern>()
+            .map(|pattern| Some(pattern.to_string()))
+            .map_err(|e| e.to_string()),
+        _ => Ok(None),
     }
+}
 
-    // Watch new path
+fn send_watch_command(watcher: &WatcherState, command: WatchCommand) -> Result<(), String> {
     let sender_opt = {
         let sender_guard = watcher
             .lock()
@@ -136,36 +390,212 @@ pub async fn set_journal_files_path(
     };
     if let Some(sender) = sender_opt {
         sender
-            .send(WatchCommand::Watch(path.to_string()))
+            .send(command)
             .map_err(|e| format!("Failed to send watch command: {}", e))?;
     }
+    Ok(())
+}
 
-    println!("path: {:#?}", path);
+/// Sets the global filename pattern entry dates are read from. Files whose name no
+/// longer gives a date, and that have no date field to fall back on, are dropped.
+#[tauri::command]
+pub async fn set_filename_pattern(
+    db: tauri::State<'_, DbConnection>,
+    app: tauri::AppHandle,
+    pattern: String,
+) -> Result<(), String> {
+    let filename_pattern = pattern
+        .parse::<FilenamePattern>()
+        .map_err(|e| e.to_string())?;
+
+    set_setting(&db, FILENAME_PATTERN_SETTING, &filename_pattern.to_string())
+        .map_err(|e| format!("Failed to set filename pattern: {}", e))?;
+    let removed = purge_non_journal_files(&db).map_err(|e| e.to_string())?;
+    emit_files_removed(&app, &removed);
+    // Entry dates may resolve differently now, so every file has to be read again.
+    clear_file_meta(&db).map_err(|e| e.to_string())?;
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
 
     Ok(())
 }
 
+#[tauri::command]
+pub async fn set_date_field(
+    db: tauri::State<'_, DbConnection>,
+    app: tauri::AppHandle,
+    field: Option<String>,
+    prefer_date_field: bool,
+) -> Result<(), String> {
+    let field = field.unwrap_or_default();
+    set_setting(&db, DATE_FIELD_SETTING, field.trim())
+        .map_err(|e| format!("Failed to set date field: {}", e))?;
+    set_setting(
+        &db,
+        PREFER_DATE_FIELD_SETTING,
+        &prefer_date_field.to_string(),
+    )
+    .map_err(|e| format!("Failed to set date field preference: {}", e))?;
+    clear_file_meta(&db).map_err(|e| e.to_string())?;
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    Ok(())
+}
+
+/// Sets which files under the journal roots are read. Files the new globs leave out
+/// are dropped along with their metrics.
+#[tauri::command]
+pub async fn set_journal_globs(
+    db: tauri::State<'_, DbConnection>,
+    app: tauri::AppHandle,
+    include_globs: Vec<String>,
+    exclude_globs: Vec<String>,
+) -> Result<(), String> {
+    validate_globs(&include_globs).map_err(|e| e.to_string())?;
+    validate_globs(&exclude_globs).map_err(|e| e.to_string())?;
+
+    set_list_setting(&db, INCLUDE_GLOBS_SETTING, &include_globs)
+        .map_err(|e| format!("Failed to set include globs: {}", e))?;
+    set_list_setting(&db, EXCLUDE_GLOBS_SETTING, &exclude_globs)
+        .map_err(|e| format!("Failed to set exclude globs: {}", e))?;
+    let removed = purge_non_journal_files(&db).map_err(|e| e.to_string())?;
+    emit_files_removed(&app, &removed);
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_inline_fields(
+    db: tauri::State<'_, DbConnection>,
+    app: tauri::AppHandle,
+    enabled: bool,
+    precedence: FieldPrecedence,
+) -> Result<(), String> {
+    set_setting(&db, INLINE_FIELDS_SETTING, &enabled.to_string())
+        .map_err(|e| format!("Failed to set inline fields: {}", e))?;
+    set_setting(&db, FIELD_PRECEDENCE_SETTING, precedence.as_str())
+        .map_err(|e| format!("Failed to set field precedence: {}", e))?;
+    // Values may now come from a different place, so every file has to be read again.
+    clear_file_meta(&db).map_err(|e| e.to_string())?;
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    Ok(())
+}
+
+/// Sets how long the watcher waits after a file last changed before reading it, so a
+/// note saved several times in a row is read once. Takes effect with the next change.
+#[tauri::command]
+pub fn set_watch_debounce(
+    db: tauri::State<'_, DbConnection>,
+    configs: tauri::State<'_, IngestConfigCache>,
+    debounce_ms: u64,
+) -> Result<(), String> {
+    set_setting(&db, WATCH_DEBOUNCE_SETTING, &debounce_ms.to_string())
+        .map_err(|e| format!("Failed to set watch debounce: {}", e))?;
+    configs.invalidate();
+    Ok(())
+}
+
+/// Sets how often, in minutes, the journal is checked for changes the watcher missed.
+/// 0 turns the periodic check off; the check at startup always runs.
+#[tauri::command]
+pub fn set_reconcile_interval(
+    db: tauri::State<'_, DbConnection>,
+    minutes: u64,
+) -> Result<(), String> {
+    set_setting(&db, RECONCILE_INTERVAL_SETTING, &minutes.to_string())
+        .map_err(|e| format!("Failed to set reconcile interval: {}", e))
+}
+
 #[tauri::command]
 pub fn delete_metric(
+    app: AppHandle,
     db: tauri::State<'_, DbConnection>,
+    configs: tauri::State<'_, IngestConfigCache>,
     metric_name: String,
 ) -> Result<(), String> {
-    let conn = db
-        .lock()
-        .map_err(|e| format!("Failed to lock connection: {}", e))?;
-    let sql = r"
-        DELETE FROM metrics WHERE name = ?1;
-        DELETE FROM tracked_metrics WHERE value = ?1;
-    ";
-    let mut batch = Batch::new(&conn, sql);
-    while let Some(mut stmt) = batch
-        .next()
-        .map_err(|err| format!("failed to delete metric {}", err))?
+    let mut changes = MetricChanges::default();
     {
-        stmt.execute([metric_name.clone()])
-            .map_err(|e| format!("failed to delete metric {}", e))?;
+        let conn = db.write().map_err(|e| e.to_string())?;
+        add_metric_dates(&conn, &metric_name, &mut changes).map_err(|e| e.to_string())?;
+        let sql = r"
+            DELETE FROM metrics WHERE name = ?1;
+            DELETE FROM metric_events WHERE name = ?1;
+            DELETE FROM tracked_metrics WHERE value = ?1;
+        ";
+        let mut batch = Batch::new(&conn, sql);
+        while let Some(mut stmt) = batch
+            .next()
+            .map_err(|err| format!("failed to delete metric {}", err))?
+        {
+            stmt.execute([metric_name.clone()])
+                .map_err(|e| format!("failed to delete metric {}", e))?;
+        }
     }
+    configs.invalidate();
     println!("Deleted metric: {}", metric_name);
+
+    // Derived metrics that read the deleted one lose its values.
+    changes.extend(recompute_derived(&db)?);
+    emit_metrics_changed(&app, &changes);
+    Ok(())
+}
+
+/// Brings stored derived values in line with the current formulas and inputs, and
+/// returns the values that changed.
+fn recompute_derived(db: &DbConnection) -> Result<MetricChanges, String> {
+    let derived = get_derived_metrics_from_db(db).map_err(|e| e.to_string())?;
+    recompute_derived_metrics(db, &derived)
+        .map_err(|e| format!("Failed to recompute derived metrics: {}", e))
+}
+
+/// Adds a metric computed from others by a formula, such as `pages_read * 2` or
+/// `focus_hours >= 3`, or changes the formula of one. Its values are computed from
+/// those already stored, so no resync is needed. Without a type, conditions make a
+/// boolean metric and anything else a float.
+#[tauri::command]
+pub fn set_derived_metric(
+    app: AppHandle,
+    db: tauri::State<'_, DbConnection>,
+    configs: tauri::State<'_, IngestConfigCache>,
+    metric_name: String,
+    formula: String,
+    metric_type: Option<MetricType>,
+) -> Result<(), String> {
+    let metric_name = metric_name.trim();
+    if metric_name.is_empty() {
+        return Err("A derived metric needs a name".to_string());
+    }
+    let formula = formula.trim();
+    let metric_type = match metric_type {
+        Some(metric_type) => metric_type,
+        None => DerivedMetric::infer_type(formula).map_err(|e| e.to_string())?,
+    };
+    let metric =
+        DerivedMetric::new(metric_name, formula, metric_type).map_err(|e| e.to_string())?;
+
+    let mut derived = get_derived_metrics_from_db(&db).map_err(|e| e.to_string())?;
+    derived.retain(|other| other.name != metric_name);
+    derived.push(metric);
+    in_dependency_order(derived).map_err(|e| e.to_string())?;
+
+    save_derived_metric(&db, metric_name, metric_type, formula).map_err(|e| e.to_string())?;
+    configs.invalidate();
+
+    let changes = recompute_derived(&db)?;
+    emit_metrics_changed(&app, &changes);
     Ok(())
 }
 
@@ -173,38 +603,286 @@ pub fn delete_metric(
 pub async fn add_metric(
     db: tauri::State<'_, DbConnection>,
     metric_name: String,
+    metric_type: Option<MetricType>,
+    checkbox_label: Option<String>,
+    unit: Option<String>,
     app: tauri::AppHandle,
 ) -> Result<(), String> {
+    let checkbox_label = validate_checkbox_label(checkbox_label)?;
+    // Checklist items can only say done or not done.
+    let metric_type = match (metric_type, &checkbox_label) {
+        (Some(metric_type), _) => metric_type,
+        (None, Some(_)) => MetricType::Boolean,
+        (None, None) => MetricType::default(),
+    };
+    let unit = validate_unit(unit, metric_type)?;
+
     {
-        let conn = db
-            .lock()
-            .map_err(|e| format!("Failed to lock connection: {}", e))?;
+        let conn = db.write().map_err(|e| e.to_string())?;
 
         // Insert into tracked_metrics table
         let mut stmt = conn
-            .prepare("INSERT OR REPLACE INTO tracked_metrics (value) VALUES (?1)")
+            .prepare(
+                "INSERT OR REPLACE INTO tracked_metrics (value, metric_type, checkbox_label, unit) VALUES (?1, ?2, ?3, ?4)",
+            )
             .map_err(|e| format!("Failed to prepare statement: {}", e))?;
 
-        stmt.execute([metric_name.clone()])
-            .map_err(|e| format!("Failed to add metric: {}", e))?;
+        stmt.execute(params![
+            metric_name,
+            metric_type.to_string(),
+            checkbox_label,
+            unit
+        ])
+        .map_err(|e| format!("Failed to add metric: {}", e))?;
     }
 
     println!("Added metric: {}", metric_name);
 
-    let journal_path = get_journal_files_path(&db).map_err(|e| e.to_string())?;
-    if let Some(path) = journal_path {
-        resync_database(app, &path)
-            .await
-            .map_err(|e| format!("Failed to resync database: {}", e))?;
+    // Files already read may hold values for the new metric.
+    clear_file_meta(&db).map_err(|e| e.to_string())?;
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    Ok(())
+}
+
+/// Sets or clears the checklist items that record a tracked metric as done.
+#[tauri::command]
+pub async fn set_metric_checkbox_label(
+    db: tauri::State<'_, DbConnection>,
+    app: tauri::AppHandle,
+    metric_name: String,
+    checkbox_label: Option<String>,
+) -> Result<(), String> {
+    let checkbox_label = validate_checkbox_label(checkbox_label)?;
+
+    {
+        let conn = db.write().map_err(|e| e.to_string())?;
+
+        let updated = conn
+            .execute(
+                "UPDATE tracked_metrics SET checkbox_label = ?1 WHERE value = ?2",
+                params![checkbox_label, metric_name],
+            )
+            .map_err(|e| format!("Failed to set checkbox label: {}", e))?;
+        if updated == 0 {
+            return Err(format!("{} is not a tracked metric", metric_name));
+        }
+
+        // Completions recorded under the old label may no longer apply.
+        delete_metric_rows(&conn, &metric_name)
+            .map_err(|e| format!("Failed to clear {}: {}", metric_name, e))?;
     }
+    clear_file_meta(&db).map_err(|e| e.to_string())?;
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    Ok(())
+}
+
+/// Sets or clears the unit a metric is measured in. Values already read may have been
+/// converted to the old unit, so the metric is read from the journal again.
+#[tauri::command]
+pub async fn set_metric_unit(
+    db: tauri::State<'_, DbConnection>,
+    app: tauri::AppHandle,
+    metric_name: String,
+    unit: Option<String>,
+) -> Result<(), String> {
+    let metric_type = get_metric_type(&db, &metric_name).map_err(|e| e.to_string())?;
+    let unit = validate_unit(unit, metric_type)?;
+
+    {
+        let conn = db.write().map_err(|e| e.to_string())?;
+
+        let updated = conn
+            .execute(
+                "UPDATE tracked_metrics SET unit = ?1 WHERE value = ?2 AND formula IS NULL",
+                params![unit, metric_name],
+            )
+            .map_err(|e| format!("Failed to set unit: {}", e))?;
+        if updated == 0 {
+            return Err(format!("{} is not a metric read from notes", metric_name));
+        }
+
+        delete_metric_rows(&conn, &metric_name)
+            .map_err(|e| format!("Failed to clear {}: {}", metric_name, e))?;
+    }
+    clear_file_meta(&db).map_err(|e| e.to_string())?;
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
 
     Ok(())
 }
 
+/// Sets how the values a metric is logged with more than once a day combine into the
+/// day's value. Stored entries are recombined in place, so no resync is needed.
+/// Aggregations that don't suit the metric's type, such as summing a boolean, are
+/// refused.
+#[tauri::command]
+pub fn set_metric_aggregation(
+    app: AppHandle,
+    db: tauri::State<'_, DbConnection>,
+    configs: tauri::State<'_, IngestConfigCache>,
+    metric_name: String,
+    aggregation: Aggregation,
+) -> Result<(), String> {
+    let metric_type = get_metric_type(&db, &metric_name).map_err(|e| e.to_string())?;
+    if !aggregation.supports(metric_type) {
+        return Err(format!(
+            "{} metrics can't be combined with {}",
+            metric_type, aggregation
+        ));
+    }
+
+    {
+        let conn = db.write().map_err(|e| e.to_string())?;
+        let updated = conn
+            .execute(
+                "UPDATE tracked_metrics SET aggregation = ?1 WHERE value = ?2",
+                params![aggregation.as_str(), metric_name],
+            )
+            .map_err(|e| format!("Failed to set aggregation: {}", e))?;
+        if updated == 0 {
+            return Err(format!("{} is not a tracked metric", metric_name));
+        }
+    }
+    configs.invalidate();
+
+    let mut changes = reaggregate_metric(&db, &metric_name, aggregation)
+        .map_err(|e| format!("Failed to recombine {}: {}", metric_name, e))?;
+    changes.extend(recompute_derived(&db)?);
+    emit_metrics_changed(&app, &changes);
+    Ok(())
+}
+
+/// Sets the other keys a metric has been logged under. Aliases that were tracked as
+/// metrics of their own are folded into this one, so their history merges with it.
+#[tauri::command]
+pub async fn set_metric_aliases(
+    db: tauri::State<'_, DbConnection>,
+    app: tauri::AppHandle,
+    metric_name: String,
+    aliases: Vec<String>,
+) -> Result<(), String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for alias in aliases {
+        let alias = alias.trim();
+        if !alias.is_empty() && alias != metric_name && !cleaned.iter().any(|a| a == alias) {
+            cleaned.push(alias.to_string());
+        }
+    }
+    let aliases_json = serde_json::to_string(&cleaned).map_err(|e| e.to_string())?;
+
+    {
+        let mut conn = db.write().map_err(|e| e.to_string())?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let updated = tx
+            .execute(
+                "UPDATE tracked_metrics SET aliases = ?1 WHERE value = ?2",
+                params![aliases_json, metric_name],
+            )
+            .map_err(|e| format!("Failed to set aliases: {}", e))?;
+        if updated == 0 {
+            return Err(format!("{} is not a tracked metric", metric_name));
+        }
+
+        // Rows stored under an alias are read again below under the metric's name.
+        for alias in &cleaned {
+            tx.execute("DELETE FROM tracked_metrics WHERE value = ?1", [alias])
+                .map_err(|e| format!("Failed to merge {}: {}", alias, e))?;
+            delete_metric_rows(&tx, alias)
+                .map_err(|e| format!("Failed to merge {}: {}", alias, e))?;
+        }
+        tx.commit()
+            .map_err(|e| format!("Failed to set aliases: {}", e))?;
+    }
+    clear_file_meta(&db).map_err(|e| e.to_string())?;
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_normalize_metric_keys(
+    db: tauri::State<'_, DbConnection>,
+    app: tauri::AppHandle,
+    enabled: bool,
+) -> Result<(), String> {
+    set_setting(&db, NORMALIZE_METRIC_KEYS_SETTING, &enabled.to_string())
+        .map_err(|e| format!("Failed to set key normalization: {}", e))?;
+    clear_file_meta(&db).map_err(|e| e.to_string())?;
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    Ok(())
+}
+
+/// Chooses which journal formats are read, such as `["markdown", "org"]`. Files of
+/// formats that are turned off are dropped along with their metrics.
+#[tauri::command]
+pub async fn set_source_formats(
+    db: tauri::State<'_, DbConnection>,
+    app: tauri::AppHandle,
+    formats: Vec<String>,
+) -> Result<(), String> {
+    let source_formats = SourceFormats::from_names(&formats).map_err(|e| e.to_string())?;
+
+    set_list_setting(&db, SOURCE_FORMATS_SETTING, &source_formats.names())
+        .map_err(|e| format!("Failed to set journal formats: {}", e))?;
+    let removed = delete_file_rows_where(&db, |path| source_formats.for_path(path).is_none())
+        .map_err(|e| e.to_string())?;
+    emit_files_removed(&app, &removed);
+
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
+
+    Ok(())
+}
+
+/// Only integer and float metrics take a unit; the others are measured in the unit
+/// their type implies, if any. Known units are stored under their usual symbol.
+fn validate_unit(unit: Option<String>, metric_type: MetricType) -> Result<Option<String>, String> {
+    let Some(unit) = unit.filter(|unit| !unit.trim().is_empty()) else {
+        return Ok(None);
+    };
+    if !matches!(metric_type, MetricType::Integer | MetricType::Float) {
+        return Err(format!("{} metrics can't have a unit", metric_type));
+    }
+    let unit = unit.parse::<Unit>().map_err(|e| e.to_string())?;
+    Ok(Some(unit.symbol().to_string()))
+}
+
+/// Checks a checkbox label, treating a blank one as "no checkbox".
+fn validate_checkbox_label(label: Option<String>) -> Result<Option<String>, String> {
+    match label.as_deref().map(str::trim) {
+        Some(label) if !label.is_empty() => label
+            .parse::<CheckboxLabel>()
+            .map(|label| Some(label.to_string()))
+            .map_err(|e| e.to_string()),
+        _ => Ok(None),
+    }
+}
+
 #[tauri::command]
 pub fn is_journal_path_configured(db: tauri::State<'_, DbConnection>) -> Result<bool, String> {
-    let journal_path = get_journal_files_path(&db).map_err(|e| e.to_string())?;
-    Ok(journal_path.is_some())
+    let journal_roots = get_enabled_journal_roots(&db).map_err(|e| e.to_string())?;
+    Ok(!journal_roots.is_empty())
 }
 
 #[tauri::command]
@@ -213,22 +891,45 @@ pub async fn udpate_metric(
     db: tauri::State<'_, DbConnection>,
     prev_name: String,
     new_name: String,
+    metric_type: Option<MetricType>,
 ) -> Result<(), String> {
+    if new_name != prev_name {
+        let dependents = get_derived_metrics_from_db(&db)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|derived| derived.inputs().any(|input| input == prev_name))
+            .map(|derived| derived.name)
+            .collect::<Vec<_>>();
+        if !dependents.is_empty() {
+            return Err(format!(
+                "{} is used in the formulas of {}, change those first",
+                prev_name,
+                dependents.join(", ")
+            ));
+        }
+    }
+
+    let mut changes = MetricChanges::default();
     {
-        let conn = db
-            .lock()
-            .map_err(|e| format!("Failed to lock connection: {}", e))?;
+        // A rename that fails partway would leave values under both names.
+        let mut conn = db.write().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        add_metric_dates(&tx, &prev_name, &mut changes).map_err(|e| e.to_string())?;
 
         let sql = r"
             UPDATE metrics
             SET name = ?1
             WHERE name = ?2;
 
+            UPDATE metric_events
+            SET name = ?1
+            WHERE name = ?2;
+
             UPDATE tracked_metrics 
             SET value = ?1
             WHERE value = ?2;
         ";
-        let mut batch = Batch::new(&conn, sql);
+        let mut batch = Batch::new(&tx, sql);
         while let Some(mut stmt) = batch
             .next()
             .map_err(|err| format!("failed to update metric {}", err))?
@@ -236,15 +937,70 @@ pub async fn udpate_metric(
             stmt.execute([new_name.clone(), prev_name.clone()])
                 .map_err(|e| format!("failed to update metric {}", e))?;
         }
+        add_metric_dates(&tx, &new_name, &mut changes).map_err(|e| e.to_string())?;
+
+        if let Some(metric_type) = metric_type {
+            // Stored values were parsed under the old type, so drop them and let the
+            // resync below re-read them. An aggregation or unit the new type can't use
+            // goes back to the type's default.
+            let sql = r"
+                UPDATE tracked_metrics
+                SET metric_type = ?1
+                WHERE value = ?2;
+
+                DELETE FROM metrics
+                WHERE name = ?2;
+
+                DELETE FROM metric_events
+                WHERE name = ?2;
+            ";
+            let mut batch = Batch::new(&tx, sql);
+            while let Some(mut stmt) = batch
+                .next()
+                .map_err(|err| format!("failed to update metric type {}", err))?
+            {
+                stmt.execute([metric_type.to_string(), new_name.clone()])
+                    .map_err(|e| format!("failed to update metric type {}", e))?;
+            }
+
+            let (aggregation, unit): (Option<String>, Option<String>) = tx
+                .query_row(
+                    "SELECT aggregation, unit FROM tracked_metrics WHERE value = ?1",
+                    [&new_name],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?
+                .unwrap_or_default();
+            if parse_aggregation(aggregation.as_deref(), metric_type)
+                .is_ok_and(|aggregation| !aggregation.supports(metric_type))
+            {
+                tx.execute(
+                    "UPDATE tracked_metrics SET aggregation = NULL WHERE value = ?1",
+                    [&new_name],
+                )
+                .map_err(|e| format!("failed to update metric type {}", e))?;
+            }
+            if validate_unit(unit, metric_type).is_err() {
+                tx.execute(
+                    "UPDATE tracked_metrics SET unit = NULL WHERE value = ?1",
+                    [&new_name],
+                )
+                .map_err(|e| format!("failed to update metric type {}", e))?;
+            }
+        }
+        tx.commit()
+            .map_err(|e| format!("failed to update metric {}", e))?;
     }
+    // The values move to the new name now; the resync below reports what it re-reads.
+    emit_metrics_changed(&app, &changes);
 
-    let journal_path = get_journal_files_path(&db).map_err(|e| e.to_string())?;
+    // The renamed metric may be set under different keys.
+    clear_file_meta(&db).map_err(|e| e.to_string())?;
 
-    if let Some(path) = journal_path {
-        resync_database(app, &path)
-            .await
-            .map_err(|e| format!("Failed to resync database: {}", e))?;
-    }
+    resync_database(app)
+        .await
+        .map_err(|e| format!("Failed to resync database: {}", e))?;
 
     Ok(())
 }
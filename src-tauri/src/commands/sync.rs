@@ -0,0 +1,10 @@
+use tauri::State;
+
+use crate::core::sync_queue::{SyncQueue, SyncStatus};
+
+/// How many files the sync workers have read, failed to read and have left, counted
+/// from when they were last idle.
+#[tauri::command]
+pub fn get_sync_status(queue: State<'_, SyncQueue>) -> Result<SyncStatus, String> {
+    Ok(queue.status())
+}
@@ -1,4 +1,8 @@
-use crate::{core::read_journal::DB_DATE_FORMAT, db::utils::get_all_habits, DbConnection};
+use crate::{
+    core::read_journal::DB_DATE_FORMAT,
+    db::utils::{get_all_habits, get_metric_units},
+    DbConnection,
+};
 use anyhow::anyhow;
 use chrono::{Days, NaiveDate};
 use serde::Serialize;
@@ -6,7 +10,7 @@ use std::collections::HashMap;
 
 #[derive(Serialize)]
 pub struct DataPoint {
-    value: i32,
+    value: f64,
     date: String,
 }
 
@@ -14,6 +18,7 @@ pub struct DataPoint {
 #[serde(rename_all = "camelCase")]
 pub struct HabitData {
     habit_name: String,
+    unit: Option<String>,
     data: Vec<DataPoint>,
 }
 
@@ -23,16 +28,15 @@ pub fn get_acitivity_data(
     end_date: NaiveDate,
 ) -> Result<Vec<HabitData>, anyhow::Error> {
     let current_habits = get_all_habits(db)?;
+    let mut units = get_metric_units(db)?;
     let mut habit_data: Vec<HabitData> = Vec::new();
 
-    let conn = db
-        .lock()
-        .map_err(|e| anyhow!("Failed to lock connection: {}", e))?;
+    let conn = db.read()?;
 
     let mut stmt = conn.prepare(
         "SELECT name, date, value 
          FROM metrics 
-         WHERE date BETWEEN ?1 AND ?2", //inclusive in sqlite
+         WHERE period = 'day' AND date BETWEEN ?1 AND ?2", //inclusive in sqlite
     )?;
 
     let mut rows = stmt.query(rusqlite::params![
@@ -40,12 +44,12 @@ pub fn get_acitivity_data(
         end_date.format(DB_DATE_FORMAT).to_string(),
     ])?;
 
-    let mut values: HashMap<(String, String), i32> = HashMap::new();
+    let mut values: HashMap<(String, String), f64> = HashMap::new();
 
     while let Some(row) = rows.next()? {
         let name: String = row.get(0)?;
         let date: String = row.get(1)?;
-        let value: i32 = row.get(2)?;
+        let value: f64 = row.get(2)?;
         values.insert((name, date), value);
     }
 
@@ -58,7 +62,7 @@ pub fn get_acitivity_data(
             let value = values
                 .get(&(habit.clone(), date_str.clone()))
                 .copied()
-                .unwrap_or(0);
+                .unwrap_or(0.0);
 
             data_points.push(DataPoint {
                 value,
@@ -71,6 +75,7 @@ pub fn get_acitivity_data(
         }
 
         habit_data.push(HabitData {
+            unit: units.remove(&habit),
             habit_name: habit,
             data: data_points,
         });
@@ -0,0 +1,126 @@
+use std::collections::BTreeSet;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::core::read_journal::DB_DATE_FORMAT;
+
+/// The metrics whose stored values changed, and the span of dates those values are
+/// stored under.
+#[derive(Debug, Default, PartialEq)]
+pub struct MetricChanges {
+    names: BTreeSet<String>,
+    dates: Option<(NaiveDate, NaiveDate)>,
+}
+
+impl MetricChanges {
+    pub fn add(&mut self, name: &str, date: NaiveDate) {
+        self.names.insert(name.to_string());
+        self.widen(date, date);
+    }
+
+    pub fn extend(&mut self, other: MetricChanges) {
+        self.names.extend(other.names);
+        if let Some((start, end)) = other.dates {
+            self.widen(start, end);
+        }
+    }
+
+    fn widen(&mut self, start: NaiveDate, end: NaiveDate) {
+        self.dates = Some(match self.dates {
+            Some((first, last)) => (first.min(start), last.max(end)),
+            None => (start, end),
+        });
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    /// The earliest and latest dates of the changed values.
+    pub fn dates(&self) -> Option<(NaiveDate, NaiveDate)> {
+        self.dates
+    }
+}
+
+/// Files whose stored rows were dropped, or moved to another path.
+#[derive(Debug, Default, PartialEq)]
+pub struct RemovedFiles {
+    pub paths: Vec<String>,
+    /// The metrics the dropped rows held.
+    pub changes: MetricChanges,
+}
+
+impl RemovedFiles {
+    pub fn extend(&mut self, other: RemovedFiles) {
+        self.paths.extend(other.paths);
+        self.changes.extend(other.changes);
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MetricsChanged {
+    names: Vec<String>,
+    start_date: String,
+    end_date: String,
+}
+
+/// Sent for each journal file whose metrics were stored, with the date they were
+/// stored under.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileIngested {
+    pub file_path: String,
+    pub date: String,
+    pub metrics: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileRemoved {
+    file_path: String,
+}
+
+/// Tells the frontend which metrics changed between which dates, so it can refresh
+/// only the views showing them.
+pub fn emit_metrics_changed(app_handle: &AppHandle, changes: &MetricChanges) {
+    let Some((start, end)) = changes.dates() else {
+        return;
+    };
+    emit(
+        app_handle,
+        "metrics-changed",
+        MetricsChanged {
+            names: changes.names().map(str::to_string).collect(),
+            start_date: start.format(DB_DATE_FORMAT).to_string(),
+            end_date: end.format(DB_DATE_FORMAT).to_string(),
+        },
+    );
+}
+
+pub fn emit_file_ingested(app_handle: &AppHandle, file: FileIngested) {
+    emit(app_handle, "file-ingested", file);
+}
+
+/// Sends `file-removed` for each removed file, then one `metrics-changed` for the
+/// values they held.
+pub fn emit_files_removed(app_handle: &AppHandle, removed: &RemovedFiles) {
+    for path in &removed.paths {
+        emit(
+            app_handle,
+            "file-removed",
+            FileRemoved {
+                file_path: path.clone(),
+            },
+        );
+    }
+    emit_metrics_changed(app_handle, &removed.changes);
+}
+
+fn emit<S: Serialize + Clone>(app_handle: &AppHandle, event: &str, payload: S) {
+    if let Err(e) = app_handle.emit(event, payload) {
+        eprintln!("Failed to emit {} event: {}", event, e);
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+use crate::core::inline_fields::prose_lines;
+
+/// Prefix that marks a checkbox label as a regex rather than exact text.
+const REGEX_PREFIX: &str = "regex:";
+
+/// A Markdown task list item: `- [x] meditate`, `* [ ] stretch` or `1. [x] read`.
+static CHECKBOX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:>\s*)*(?:[-*+]|\d+[.)])\s+\[(.)\]\s+(.*?)\s*$")
+        .expect("valid checkbox regex")
+});
+
+/// A checklist item in a note body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkbox {
+    pub label: String,
+    pub checked: bool,
+}
+
+/// Collects the checklist items of a note body, skipping fenced code blocks. Only `x`
+/// counts as done; other markers some plugins use, such as `-` for cancelled or `/` for
+/// in progress, count as not done.
+pub fn parse_checkboxes(body: &str) -> Vec<Checkbox> {
+    prose_lines(body)
+        .filter_map(|(_, line)| CHECKBOX.captures(line))
+        .filter(|captures| !captures[2].is_empty())
+        .map(|captures| Checkbox {
+            label: captures[2].to_string(),
+            checked: matches!(&captures[1], "x" | "X"),
+        })
+        .collect()
+}
+
+/// Which checklist items count as a tracked habit.
+///
+/// Stored either as the item's text, matched ignoring case and surrounding whitespace,
+/// or as `regex:` followed by a regex searched for in the item's text.
+#[derive(Debug, Clone)]
+pub enum CheckboxLabel {
+    Exact(String),
+    Regex(Regex),
+}
+
+impl CheckboxLabel {
+    pub fn matches(&self, label: &str) -> bool {
+        match self {
+            CheckboxLabel::Exact(expected) => {
+                label.trim().to_lowercase() == expected.to_lowercase()
+            }
+            CheckboxLabel::Regex(regex) => regex.is_match(label),
+        }
+    }
+
+    /// Whether the habit was completed according to the note's checklist: done if any
+    /// matching item is checked, missed if matching items exist but none is, and `None`
+    /// if the checklist doesn't mention it.
+    pub fn completion(&self, checkboxes: &[Checkbox]) -> Option<bool> {
+        checkboxes
+            .iter()
+            .filter(|checkbox| self.matches(&checkbox.label))
+            .map(|checkbox| checkbox.checked)
+            .reduce(|done, checked| done || checked)
+    }
+}
+
+impl PartialEq for CheckboxLabel {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_string() == other.to_string()
+    }
+}
+
+impl FromStr for CheckboxLabel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(pattern) = s.strip_prefix(REGEX_PREFIX) {
+            let regex = Regex::new(pattern)
+                .map_err(|e| anyhow!("Invalid checkbox regex {}: {}", pattern, e))?;
+            return Ok(CheckboxLabel::Regex(regex));
+        }
+
+        let label = s.trim();
+        if label.is_empty() {
+            return Err(anyhow!("Checkbox label is empty"));
+        }
+        Ok(CheckboxLabel::Exact(label.to_string()))
+    }
+}
+
+impl fmt::Display for CheckboxLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckboxLabel::Exact(label) => f.write_str(label),
+            CheckboxLabel::Regex(regex) => write!(f, "{}{}", REGEX_PREFIX, regex.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = "## Habits
+- [x] Meditate
+- [ ] stretch
+* [X] Read 20 pages
+- [-] run
+1. [x] journal
+
+```
+- [x] stretch
+```
+";
+
+    fn label(label: &str) -> CheckboxLabel {
+        label.parse().expect("Failed to parse checkbox label")
+    }
+
+    #[test]
+    fn test_parse_checkboxes() {
+        let checkboxes = parse_checkboxes(BODY);
+
+        assert_eq!(checkboxes.len(), 5);
+        assert_eq!(
+            checkboxes[0],
+            Checkbox {
+                label: "Meditate".to_string(),
+                checked: true
+            }
+        );
+        assert!(!checkboxes[1].checked);
+        assert!(!checkboxes[3].checked);
+        assert_eq!(checkboxes[4].label, "journal");
+    }
+
+    #[test]
+    fn test_labels_map_to_completions() {
+        let checkboxes = parse_checkboxes(BODY);
+
+        assert_eq!(label("meditate").completion(&checkboxes), Some(true));
+        assert_eq!(label("stretch").completion(&checkboxes), Some(false));
+        assert_eq!(
+            label("regex:^Read \\d+ pages$").completion(&checkboxes),
+            Some(true)
+        );
+        assert_eq!(label("walk").completion(&checkboxes), None);
+        assert!("regex:(".parse::<CheckboxLabel>().is_err());
+    }
+}
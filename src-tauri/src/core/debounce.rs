@@ -0,0 +1,228 @@
+use std::{
+    collections::BTreeSet,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+/// What waiting on an [`EventSource`] gave.
+#[derive(Debug, PartialEq)]
+pub enum Next<T> {
+    Event(T),
+    TimedOut,
+    /// No more events will arrive.
+    Closed,
+}
+
+/// Where debounced events come from, along with the clock they are timed by.
+pub trait EventSource {
+    type Event;
+
+    fn now(&self) -> Instant;
+
+    /// Waits for the next event, for at most `timeout` when one is given.
+    fn next_event(&mut self, timeout: Option<Duration>) -> Next<Self::Event>;
+}
+
+impl<T> EventSource for mpsc::Receiver<T> {
+    type Event = T;
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn next_event(&mut self, timeout: Option<Duration>) -> Next<T> {
+        match timeout {
+            Some(timeout) => match self.recv_timeout(timeout) {
+                Ok(event) => Next::Event(event),
+                Err(RecvTimeoutError::Timeout) => Next::TimedOut,
+                Err(RecvTimeoutError::Disconnected) => Next::Closed,
+            },
+            None => self.recv().map_or(Next::Closed, Next::Event),
+        }
+    }
+}
+
+/// Collects changed paths until none has been added for a quiet period, then hands
+/// them over all at once. A file saved several times in a row is read once, after the
+/// last save, even if nothing else changes afterwards.
+#[derive(Debug)]
+pub struct Debouncer {
+    quiet_period: Duration,
+    /// Flush right away once this many paths are waiting.
+    max_pending: usize,
+    pending: BTreeSet<String>,
+    deadline: Option<Instant>,
+}
+
+impl Debouncer {
+    pub fn new(quiet_period: Duration, max_pending: usize) -> Self {
+        Debouncer {
+            quiet_period,
+            max_pending,
+            pending: BTreeSet::new(),
+            deadline: None,
+        }
+    }
+
+    /// Applies to paths added from now on.
+    pub fn set_quiet_period(&mut self, quiet_period: Duration) {
+        self.quiet_period = quiet_period;
+    }
+
+    /// Adds a path and restarts the quiet period.
+    pub fn push(&mut self, path: String, now: Instant) {
+        self.pending.insert(path);
+        self.deadline = Some(now + self.quiet_period);
+    }
+
+    /// Drops waiting paths, such as those of files deleted before they were flushed.
+    pub fn retain(&mut self, keep: impl FnMut(&String) -> bool) {
+        self.pending.retain(keep);
+        if self.pending.is_empty() {
+            self.deadline = None;
+        }
+    }
+
+    /// How long until the waiting paths are due, or `None` when nothing is waiting.
+    pub fn time_left(&self, now: Instant) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(now))
+    }
+
+    /// The waiting paths, if the quiet period has passed or too many are waiting.
+    pub fn take_due(&mut self, now: Instant) -> Vec<String> {
+        let due = self.deadline.is_some_and(|deadline| now >= deadline)
+            || self.pending.len() >= self.max_pending;
+        if due {
+            self.take_all()
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn take_all(&mut self) -> Vec<String> {
+        self.deadline = None;
+        std::mem::take(&mut self.pending).into_iter().collect()
+    }
+}
+
+/// Feeds events from `source` to `handle`, which adds paths to the debouncer, and
+/// passes each batch of settled paths to `flush`. Waits on the source only until the
+/// next batch is due, so a batch is flushed even when no further events arrive. Runs
+/// until the source closes, then flushes whatever is left.
+pub fn run_debounced<S: EventSource>(
+    source: &mut S,
+    debouncer: &mut Debouncer,
+    mut handle: impl FnMut(S::Event, &mut Debouncer, Instant),
+    mut flush: impl FnMut(Vec<String>),
+) {
+    loop {
+        match source.next_event(debouncer.time_left(source.now())) {
+            Next::Event(event) => handle(event, debouncer, source.now()),
+            Next::TimedOut => (),
+            Next::Closed => break,
+        }
+
+        let due = debouncer.take_due(source.now());
+        if !due.is_empty() {
+            flush(due);
+        }
+    }
+
+    let rest = debouncer.take_all();
+    if !rest.is_empty() {
+        flush(rest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, collections::VecDeque, rc::Rc};
+
+    /// Replays paths at set offsets on a clock that only moves while waiting. A path
+    /// starting with `-` stands for a deletion.
+    struct FakeSource {
+        start: Instant,
+        clock: Rc<Cell<Instant>>,
+        script: VecDeque<(Duration, &'static str)>,
+    }
+
+    impl EventSource for FakeSource {
+        type Event = &'static str;
+
+        fn now(&self) -> Instant {
+            self.clock.get()
+        }
+
+        fn next_event(&mut self, timeout: Option<Duration>) -> Next<&'static str> {
+            let limit = timeout.map(|timeout| self.clock.get() + timeout);
+            match self.script.front() {
+                Some(&(at, path)) if limit.is_none_or(|limit| self.start + at <= limit) => {
+                    self.script.pop_front();
+                    self.clock.set(self.clock.get().max(self.start + at));
+                    Next::Event(path)
+                }
+                _ => match limit {
+                    Some(limit) => {
+                        self.clock.set(limit);
+                        Next::TimedOut
+                    }
+                    None => Next::Closed,
+                },
+            }
+        }
+    }
+
+    /// The batches flushed for `script`, with the milliseconds at which each was flushed.
+    fn flushes(script: &[(u64, &'static str)], max_pending: usize) -> Vec<(u128, Vec<String>)> {
+        let start = Instant::now();
+        let clock = Rc::new(Cell::new(start));
+        let mut source = FakeSource {
+            start,
+            clock: Rc::clone(&clock),
+            script: script
+                .iter()
+                .map(|&(at, path)| (Duration::from_millis(at), path))
+                .collect(),
+        };
+        let mut debouncer = Debouncer::new(Duration::from_millis(500), max_pending);
+        let mut flushed = Vec::new();
+
+        run_debounced(
+            &mut source,
+            &mut debouncer,
+            |path, debouncer, now| match path.strip_prefix('-') {
+                Some(removed) => debouncer.retain(|pending| pending != removed),
+                None => debouncer.push(path.to_string(), now),
+            },
+            |paths| flushed.push(((clock.get() - start).as_millis(), paths)),
+        );
+        flushed
+    }
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn test_single_save_flushes_after_quiet_period() {
+        assert_eq!(flushes(&[(0, "a.md")], 20), vec![(500, paths(&["a.md"]))]);
+    }
+
+    #[test]
+    fn test_quiet_period_restarts_on_each_event() {
+        let script = [(0, "a.md"), (300, "b.md"), (600, "a.md"), (2000, "c.md")];
+        assert_eq!(
+            flushes(&script, 20),
+            vec![(1100, paths(&["a.md", "b.md"])), (2500, paths(&["c.md"]))]
+        );
+    }
+
+    #[test]
+    fn test_full_buffer_and_deletions() {
+        // A full buffer flushes at once; a deleted file is never flushed.
+        let script = [(0, "a.md"), (10, "b.md"), (20, "c.md"), (30, "-c.md")];
+        assert_eq!(flushes(&script, 2), vec![(10, paths(&["a.md", "b.md"]))]);
+    }
+}
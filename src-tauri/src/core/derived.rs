@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use evalexpr::{build_operator_tree, ContextWithMutableVariables, HashMapContext, Node, Value};
+
+use crate::core::metric_value::{MetricType, MetricValue};
+
+/// A metric computed from other metrics by a formula, such as
+/// `reading_minutes = pages_read * 2` or `deep_work_day = focus_hours >= 3`. Its values
+/// are stored alongside those of tracked metrics, so it shows up everywhere they do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivedMetric {
+    pub name: String,
+    pub metric_type: MetricType,
+    pub formula: String,
+    tree: Node,
+}
+
+impl DerivedMetric {
+    /// Parses `formula`, which may use arithmetic, comparisons, `&&`, `||`, `if(...)`
+    /// and functions such as `min` and `max`, with other metrics as variables.
+    pub fn new(name: &str, formula: &str, metric_type: MetricType) -> Result<Self> {
+        let tree = build_operator_tree(formula)
+            .map_err(|e| anyhow!("Invalid formula {}: {}", formula, e))?;
+        if tree.iter_write_variable_identifiers().next().is_some() {
+            return Err(anyhow!("Formula {} assigns to a variable", formula));
+        }
+
+        let derived = DerivedMetric {
+            name: name.to_string(),
+            metric_type,
+            formula: formula.to_string(),
+            tree,
+        };
+        if derived.inputs().any(|input| input == name) {
+            return Err(anyhow!("The formula of {} refers to itself", name));
+        }
+        Ok(derived)
+    }
+
+    /// The type of metric the formula gives when none is chosen: boolean for a
+    /// condition such as `focus_hours >= 3`, float otherwise.
+    pub fn infer_type(formula: &str) -> Result<MetricType> {
+        let derived = DerivedMetric::new("", formula, MetricType::Float)?;
+        let values = derived
+            .inputs()
+            .map(|input| (input.to_string(), 0.0))
+            .collect();
+        match derived.eval(&values) {
+            Ok(Value::Boolean(_)) => Ok(MetricType::Boolean),
+            _ => Ok(MetricType::Float),
+        }
+    }
+
+    /// The metrics the formula reads.
+    pub fn inputs(&self) -> impl Iterator<Item = &str> {
+        self.tree.iter_read_variable_identifiers()
+    }
+
+    /// Evaluates the formula over an entry's metric values. Inputs the entry doesn't
+    /// set count as 0, and `None` means it sets none of them.
+    pub fn evaluate(&self, values: &HashMap<String, f64>) -> Option<Result<MetricValue>> {
+        if !self.inputs().any(|input| values.contains_key(input)) {
+            return None;
+        }
+
+        Some(self.eval(values).and_then(|value| {
+            match value {
+                Value::Boolean(flag) => self
+                    .metric_type
+                    .value_from_number(f64::from(u8::from(flag))),
+                Value::Float(number) => self.metric_type.value_from_number(number),
+                Value::Int(number) => self.metric_type.value_from_number(number as f64),
+                other => Err(anyhow!("the formula gave {} rather than a number", other)),
+            }
+        }))
+    }
+
+    fn eval(&self, values: &HashMap<String, f64>) -> Result<Value> {
+        let mut context = HashMapContext::new();
+        for input in self.inputs() {
+            let value = values.get(input).copied().unwrap_or(0.0);
+            context
+                .set_value(input.to_string(), Value::Float(value))
+                .map_err(|e| anyhow!("{}", e))?;
+        }
+        self.tree
+            .eval_with_context(&context)
+            .map_err(|e| anyhow!("{}", e))
+    }
+}
+
+/// Orders derived metrics so each comes after the derived metrics it reads, failing if
+/// some of them read each other in a cycle.
+pub fn in_dependency_order(mut pending: Vec<DerivedMetric>) -> Result<Vec<DerivedMetric>> {
+    let mut ordered: Vec<DerivedMetric> = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|derived| {
+            derived
+                .inputs()
+                .all(|input| !pending.iter().any(|other| other.name == input))
+        });
+        match ready {
+            Some(index) => ordered.push(pending.remove(index)),
+            None => {
+                let names = pending
+                    .iter()
+                    .map(|derived| derived.name.as_str())
+                    .collect::<Vec<_>>();
+                return Err(anyhow!(
+                    "The formulas of {} depend on each other",
+                    names.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+/// Evaluates each derived metric, in order, over an entry's values. Each result is
+/// added to `values`, so later formulas can read earlier derived metrics.
+pub fn derive_values<'a>(
+    derived: &'a [DerivedMetric],
+    values: &mut HashMap<String, f64>,
+) -> Vec<(&'a DerivedMetric, Result<MetricValue>)> {
+    let mut results = Vec::new();
+    for metric in derived {
+        let Some(result) = metric.evaluate(values) else {
+            continue;
+        };
+        if let Ok(value) = &result {
+            values.insert(metric.name.clone(), value.as_f64());
+        }
+        results.push((metric, result));
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derived_values() {
+        let derived = in_dependency_order(vec![
+            DerivedMetric::new("active", "fitness > 10", MetricType::Boolean).unwrap(),
+            DerivedMetric::new("fitness", "run_km + swim_km * 4", MetricType::Float).unwrap(),
+            DerivedMetric::new("reading_minutes", "pages_read * 2", MetricType::Integer).unwrap(),
+        ])
+        .unwrap();
+        let mut values = HashMap::from([("run_km".to_string(), 5.0), ("swim_km".to_string(), 1.5)]);
+
+        let results = derive_values(&derived, &mut values)
+            .into_iter()
+            .map(|(metric, value)| (metric.name.as_str(), value.unwrap()))
+            .collect::<Vec<_>>();
+
+        // No pages were read, so reading_minutes has no value for the day.
+        assert_eq!(
+            results,
+            vec![
+                ("fitness", MetricValue::Float(11.0)),
+                ("active", MetricValue::Boolean(true))
+            ]
+        );
+        assert_eq!(
+            DerivedMetric::infer_type("focus_hours >= 3").unwrap(),
+            MetricType::Boolean
+        );
+        assert!(DerivedMetric::new("loop", "loop + 1", MetricType::Float).is_err());
+        assert!(in_dependency_order(vec![
+            DerivedMetric::new("a", "b + 1", MetricType::Float).unwrap(),
+            DerivedMetric::new("b", "a + 1", MetricType::Float).unwrap(),
+        ])
+        .is_err());
+    }
+}
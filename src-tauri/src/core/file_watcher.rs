@@ -1,12 +1,34 @@
-use notify::{recommended_watcher, RecursiveMode, Watcher};
+use notify::{
+    event::{ModifyKind, RenameMode},
+    recommended_watcher, Event, EventKind, RecursiveMode, Watcher,
+};
 use std::{
-    collections::HashSet,
-    path::Path,
+    path::{Path, PathBuf},
     sync::mpsc,
     thread,
-    time::{Duration, Instant},
 };
 use tauri::{AppHandle, Manager};
+use walkdir::WalkDir;
+
+use crate::{
+    core::{
+        change_events::{emit_files_removed, RemovedFiles},
+        debounce::{run_debounced, Debouncer},
+        ingest_cache::IngestConfigCache,
+        load_journal_filter,
+        path_filter::JournalFilter,
+        sync_queue::SyncQueue,
+    },
+    db::{
+        journal_roots::get_enabled_journal_roots,
+        utils::{delete_file_rows, get_watch_debounce, rename_file_rows, DEFAULT_WATCH_DEBOUNCE},
+    },
+    DbConnection,
+};
+
+/// Changed files waiting to be read past this count are sent on without waiting for
+/// the quiet period, so a bulk change starts syncing right away.
+const MAX_PENDING_PATHS: usize = 20;
 
 #[derive(Debug)]
 pub enum WatchCommand {
@@ -17,7 +39,7 @@ pub enum WatchCommand {
 
 pub fn start_watcher(
     app_handle: AppHandle,
-    dir: Option<String>,
+    dirs: Vec<String>,
 ) -> notify::Result<mpsc::Sender<WatchCommand>> {
     let (event_tx, event_rx) = mpsc::channel();
     let (cmd_tx, cmd_rx) = mpsc::channel::<WatchCommand>();
@@ -30,12 +52,11 @@ pub fn start_watcher(
         e
     })?;
 
-    // Watch the initial directory if provided
-    if let Some(dir) = &dir {
-        let path = Path::new(dir);
-        if let Err(e) = watcher.watch(path, RecursiveMode::NonRecursive) {
+    // Watch the initial journal roots. One missing folder shouldn't stop the others
+    // from being watched.
+    for dir in &dirs {
+        if let Err(e) = watcher.watch(Path::new(dir), RecursiveMode::Recursive) {
             eprintln!("Failed to watch initial directory {}: {:?}", dir, e);
-            return Err(e);
         }
     }
 
@@ -45,7 +66,7 @@ pub fn start_watcher(
             match cmd {
                 WatchCommand::Watch(path) => {
                     println!("Watching {}", path);
-                    if let Err(e) = watcher.watch(Path::new(&path), RecursiveMode::NonRecursive) {
+                    if let Err(e) = watcher.watch(Path::new(&path), RecursiveMode::Recursive) {
                         eprintln!("Failed to watch {}: {}", path, e);
                     }
                 }
@@ -64,68 +85,308 @@ pub fn start_watcher(
         Ok(())
     });
 
-    // Event processor thread with buffering
+    // Event processor thread. Changed files are read once they have gone a quiet
+    // period without further changes.
     thread::spawn(move || {
-        let max_buffer_size = 20;
-        let mut event_buffer: HashSet<String> = HashSet::with_capacity(max_buffer_size);
-        let mut last_flush = Instant::now();
-        let flush_interval = Duration::from_millis(1000);
-        let tx = app_handle.state::<mpsc::SyncSender<String>>().clone();
-
-        while let Ok(event_result) = event_rx.recv() {
-            match event_result {
-                Ok(event) => {
-                    match event.kind {
-                        // TODO:: handle remove event too
-                        notify::EventKind::Create(_) | notify::EventKind::Modify(_) => {
-                            for path in &event.paths {
-                                if path.is_file()
-                                    && path.extension().and_then(|s| s.to_str()) == Some("md")
-                                {
-                                    if let Some(path_str) = path.to_str() {
-                                        event_buffer.insert(path_str.to_string());
-                                    }
-                                }
-                            }
-
-                            // Flush buffer if it's time or if buffer is full
-                            let should_flush = last_flush.elapsed() >= flush_interval
-                                || event_buffer.len() >= max_buffer_size;
-
-                            if should_flush && !event_buffer.is_empty() {
-                                for path in event_buffer.drain() {
-                                    match tx.send(path) {
-                                        Ok(_) => {}
-                                        Err(e) => {
-                                            eprintln!(
-                                                "Failed to send file path to sync worker: {}",
-                                                e
-                                            );
-                                        }
-                                    }
-                                }
-                                last_flush = Instant::now();
-                            }
-                        }
-                        _ => continue, // Ignore other event kinds
+        let mut event_rx = event_rx;
+        let queue = app_handle.state::<SyncQueue>();
+        let db = app_handle.state::<DbConnection>();
+        let configs = app_handle.state::<IngestConfigCache>();
+        let mut debouncer = Debouncer::new(DEFAULT_WATCH_DEBOUNCE, MAX_PENDING_PATHS);
+        // The settings below, with the cache generation they were loaded at.
+        let mut loaded: Option<(u64, Vec<JournalFilter>)> = None;
+
+        run_debounced(
+            &mut event_rx,
+            &mut debouncer,
+            |event_result: notify::Result<Event>, debouncer, now| {
+                let event = match event_result {
+                    Ok(event) => event,
+                    Err(err) => {
+                        eprintln!("Watcher error: {err}");
+                        return;
                     }
+                };
+                if !matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                ) {
+                    return; // Ignore other event kinds
                 }
-                Err(err) => eprintln!("Watcher error: {err}"),
-            }
-        }
 
-        // Process any remaining events
-        if !event_buffer.is_empty() {
-            for path in event_buffer.drain() {
-                match tx.send(path) {
-                    Ok(_) => {}
+                // Settings may change while watching, so reload them once the commands
+                // that change them have invalidated the cached ones.
+                let generation = configs.generation();
+                if loaded
+                    .as_ref()
+                    .is_none_or(|(loaded_at, _)| *loaded_at != generation)
+                {
+                    match get_watch_debounce(&db) {
+                        Ok(quiet_period) => debouncer.set_quiet_period(quiet_period),
+                        Err(e) => eprintln!("Failed to load watch debounce: {}", e),
+                    }
+                    match current_journal_filters(&db) {
+                        Ok(filters) => loaded = Some((generation, filters)),
+                        Err(e) => {
+                            eprintln!("Failed to load journal filters: {}", e);
+                            return;
+                        }
+                    }
+                }
+                let Some((_, filters)) = &loaded else {
+                    return;
+                };
+                let changes = match apply_file_event(&event, filters, &db) {
+                    Ok(changes) => changes,
                     Err(e) => {
-                        eprintln!("Failed to send file path to sync worker: {}", e);
+                        eprintln!("Failed to apply file event {:?}: {}", event, e);
+                        return;
+                    }
+                };
+                emit_files_removed(&app_handle, &changes.purged);
+
+                // Files that are gone can't be read anymore.
+                debouncer.retain(|pending| {
+                    !changes
+                        .removed
+                        .iter()
+                        .any(|removed| Path::new(pending).starts_with(removed))
+                });
+                for path in changes.ingest {
+                    if let Some(path_str) = path.to_str() {
+                        debouncer.push(path_str.to_string(), now);
                     }
                 }
-            }
-        }
+            },
+            |paths| {
+                for path in paths {
+                    queue.push_live(path);
+                }
+            },
+        );
     });
 
     Ok(cmd_tx)
 }
+
+fn current_journal_filters(db: &DbConnection) -> Result<Vec<JournalFilter>, anyhow::Error> {
+    get_enabled_journal_roots(db)?
+        .iter()
+        .map(|root| load_journal_filter(db, &root.path))
+        .collect()
+}
+
+/// What a watcher event means for the sync worker once its removals and renames have
+/// been applied to the database.
+#[derive(Debug, Default, PartialEq)]
+struct FileChanges {
+    /// Journal files to (re)read.
+    ingest: Vec<PathBuf>,
+    /// Files or folders that no longer exist under their old path.
+    removed: Vec<PathBuf>,
+    /// The stored files whose rows were dropped or moved to a new path.
+    purged: RemovedFiles,
+}
+
+/// Purges the rows of deleted files and re-keys the rows of renamed ones right away,
+/// and returns the files the sync worker still has to read.
+///
+/// A file renamed to somewhere no root picks up (out of the journal folder, or into an
+/// excluded one) counts as deleted.
+fn apply_file_event(
+    event: &Event,
+    filters: &[JournalFilter],
+    db: &DbConnection,
+) -> Result<FileChanges, anyhow::Error> {
+    let mut changes = FileChanges::default();
+
+    match (&event.kind, event.paths.as_slice()) {
+        (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => {
+            let files = journal_files_at(to, filters);
+            if files.is_empty() {
+                changes.purged = delete_file_rows(db, from)?;
+            } else {
+                changes.purged.paths = rename_file_rows(db, from, to)?;
+            }
+            changes.removed.push(from.clone());
+            changes.ingest = files;
+        }
+        (EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)), paths) => {
+            for path in paths {
+                changes.purged.extend(delete_file_rows(db, path)?);
+                changes.removed.push(path.clone());
+            }
+        }
+        // Backends that can't pair up both halves of a rename report each path on its own.
+        (EventKind::Modify(ModifyKind::Name(_)), paths) => {
+            for path in paths {
+                if path.exists() {
+                    changes.ingest.extend(journal_files_at(path, filters));
+                } else {
+                    changes.purged.extend(delete_file_rows(db, path)?);
+                    changes.removed.push(path.clone());
+                }
+            }
+        }
+        (EventKind::Create(_) | EventKind::Modify(_), paths) => {
+            for path in paths {
+                if path.is_file() && filters.iter().any(|filter| filter.matches(path)) {
+                    changes.ingest.push(path.clone());
+                }
+            }
+        }
+        _ => (),
+    }
+
+    Ok(changes)
+}
+
+/// The journal files at `path`: the file itself, or the matching files inside a folder
+/// that was moved in.
+fn journal_files_at(path: &Path, filters: &[JournalFilter]) -> Vec<PathBuf> {
+    let is_journal_file = |path: &Path| filters.iter().any(|filter| filter.matches(path));
+
+    if path.is_dir() {
+        WalkDir::new(path)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file() && is_journal_file(entry.path()))
+            .map(|entry| entry.into_path())
+            .collect()
+    } else if path.is_file() && is_journal_file(path) {
+        vec![path.to_path_buf()]
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        metric_value::{MetricDefinition, MetricType},
+        read_journal::read_journal_file,
+        IngestConfig,
+    };
+    use notify::event::RemoveKind;
+    use std::fs;
+
+    fn setup_db() -> DbConnection {
+        DbConnection::open_in_memory().unwrap()
+    }
+
+    fn store_entry(db: &DbConnection, path: &Path, date: &str) {
+        let conn = db.write().unwrap();
+        let path = path.to_string_lossy();
+        conn.execute(
+            "INSERT INTO metrics (file_path, name, value, date, updated_at) VALUES (?1, 'pages_read', 12, ?2, ?2)",
+            [&path, date],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO file_meta (file_path, last_modified, entry_date) VALUES (?1, ?2, ?2)",
+            [&path, date],
+        )
+        .unwrap();
+    }
+
+    fn stored_paths(db: &DbConnection, table: &str) -> Vec<String> {
+        let conn = db.read().unwrap();
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT DISTINCT file_path FROM {} ORDER BY file_path",
+                table
+            ))
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<String>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_deleted_and_renamed_files_update_stored_rows() {
+        let vault = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let root = vault.path().join("Daily");
+        fs::create_dir(&root).unwrap();
+        let filters = vec![JournalFilter::new(&root, &[], &[]).unwrap()];
+        let db = setup_db();
+
+        let deleted = root.join("2025-10-20.md");
+        let renamed = root.join("2025-10-21.md");
+        let moved_out = root.join("2025-10-22.md");
+        for (path, date) in [
+            (&deleted, "2025-10-20"),
+            (&renamed, "2025-10-21"),
+            (&moved_out, "2025-10-22"),
+        ] {
+            fs::write(path, "---\npages_read: 12\n---\n").unwrap();
+            store_entry(&db, path, date);
+        }
+
+        // Delete
+        fs::remove_file(&deleted).unwrap();
+        let event = Event::new(EventKind::Remove(RemoveKind::File)).add_path(deleted.clone());
+        let changes = apply_file_event(&event, &filters, &db).unwrap();
+        assert!(changes.ingest.is_empty());
+        assert_eq!(changes.removed, vec![deleted.clone()]);
+        assert_eq!(
+            changes.purged.paths,
+            vec![deleted.to_string_lossy().to_string()]
+        );
+        assert_eq!(
+            changes.purged.changes.names().collect::<Vec<_>>(),
+            vec!["pages_read"]
+        );
+
+        // Rename within the journal folder
+        let archive = root.join("2025");
+        fs::create_dir(&archive).unwrap();
+        let renamed_to = archive.join("2025-10-21.md");
+        fs::rename(&renamed, &renamed_to).unwrap();
+        let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(renamed.clone())
+            .add_path(renamed_to.clone());
+        let changes = apply_file_event(&event, &filters, &db).unwrap();
+        assert_eq!(changes.ingest, vec![renamed_to.clone()]);
+
+        // Rename to another day, which moves the entry once it is read again
+        let redated = archive.join("2025-10-23.md");
+        fs::rename(&renamed_to, &redated).unwrap();
+        let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(renamed_to.clone())
+            .add_path(redated.clone());
+        let changes = apply_file_event(&event, &filters, &db).unwrap();
+        assert_eq!(changes.ingest, vec![redated.clone()]);
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            ..Default::default()
+        };
+        read_journal_file(redated.to_str().unwrap(), &config, &db).unwrap();
+        let date: String = db
+            .read()
+            .unwrap()
+            .query_row(
+                "SELECT date FROM metrics WHERE file_path = ?1",
+                [redated.to_string_lossy()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(date, "2025-10-23");
+
+        // Move out of the journal folder
+        let moved_to = outside.path().join("2025-10-22.md");
+        fs::rename(&moved_out, &moved_to).unwrap();
+        let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(moved_out.clone())
+            .add_path(moved_to);
+        let changes = apply_file_event(&event, &filters, &db).unwrap();
+        assert!(changes.ingest.is_empty());
+        assert_eq!(changes.removed, vec![moved_out]);
+
+        let expected = vec![redated.to_string_lossy().to_string()];
+        assert_eq!(stored_paths(&db, "metrics"), expected);
+        assert_eq!(stored_paths(&db, "file_meta"), expected);
+    }
+}
@@ -0,0 +1,176 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use anyhow::{anyhow, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::NaiveDate;
+use regex::Regex;
+
+pub const DEFAULT_FILENAME_PATTERN: &str = "%Y-%m-%d";
+
+/// Prefix that marks a stored pattern as a regex rather than a chrono format.
+const REGEX_PREFIX: &str = "regex:";
+
+/// Formats tried, in order, on the `date` group captured by a regex pattern.
+const CAPTURED_DATE_FORMATS: [&str; 7] = [
+    "%Y-%m-%d",
+    "%Y_%m_%d",
+    "%Y.%m.%d",
+    "%Y/%m/%d",
+    "%Y%m%d",
+    "%b %d, %Y",
+    "%B %d, %Y",
+];
+
+/// Describes how a journal file name (without extension) encodes its date.
+///
+/// Stored either as a chrono format that must match the whole stem, such as
+/// `journal-%Y-%m-%d`, or as `regex:` followed by a regex with a named `date` group.
+#[derive(Debug, Clone)]
+pub enum FilenamePattern {
+    Chrono(String),
+    Regex(Regex),
+}
+
+impl FilenamePattern {
+    pub fn date_from_path(&self, path: &Path) -> Option<NaiveDate> {
+        let stem = path.file_stem()?.to_str()?;
+        self.date_from_stem(stem)
+    }
+
+    pub fn date_from_stem(&self, stem: &str) -> Option<NaiveDate> {
+        match self {
+            FilenamePattern::Chrono(format) => NaiveDate::parse_from_str(stem, format).ok(),
+            FilenamePattern::Regex(regex) => {
+                let captured = strip_ordinal_suffix(regex.captures(stem)?.name("date")?.as_str());
+                CAPTURED_DATE_FORMATS
+                    .iter()
+                    .find_map(|format| NaiveDate::parse_from_str(&captured, format).ok())
+            }
+        }
+    }
+}
+
+impl Default for FilenamePattern {
+    fn default() -> Self {
+        FilenamePattern::Chrono(DEFAULT_FILENAME_PATTERN.to_string())
+    }
+}
+
+impl FromStr for FilenamePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(pattern) = s.strip_prefix(REGEX_PREFIX) {
+            let regex = Regex::new(pattern)
+                .map_err(|e| anyhow!("Invalid filename regex {}: {}", pattern, e))?;
+            if !regex.capture_names().flatten().any(|name| name == "date") {
+                return Err(anyhow!(
+                    "Filename regex {} needs a named `(?P<date>...)` group",
+                    pattern
+                ));
+            }
+            return Ok(FilenamePattern::Regex(regex));
+        }
+
+        let items = StrftimeItems::new(s).collect::<Vec<_>>();
+        if items.iter().any(|item| matches!(item, Item::Error)) {
+            return Err(anyhow!("Invalid filename date format {}", s));
+        }
+        Ok(FilenamePattern::Chrono(s.to_string()))
+    }
+}
+
+impl fmt::Display for FilenamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilenamePattern::Chrono(format) => f.write_str(format),
+            FilenamePattern::Regex(regex) => write!(f, "{}{}", REGEX_PREFIX, regex.as_str()),
+        }
+    }
+}
+
+/// Turns `Oct 20th, 2025` into `Oct 20, 2025`, the shape Logseq uses for journal titles.
+fn strip_ordinal_suffix(date: &str) -> Cow<'_, str> {
+    static ORDINAL: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(\d)(st|nd|rd|th)\b").expect("valid ordinal regex"));
+    ORDINAL.replace_all(date, "$1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    fn pattern(pattern: &str) -> FilenamePattern {
+        pattern.parse().expect("Failed to parse filename pattern")
+    }
+
+    #[test]
+    fn test_default_pattern_matches_obsidian_and_foam() {
+        let pattern = FilenamePattern::default();
+
+        assert_eq!(pattern.date_from_stem("2025-10-20"), date(2025, 10, 20));
+        assert_eq!(pattern.date_from_stem("2031-01-02"), date(2031, 1, 2));
+        assert_eq!(
+            pattern.date_from_path(Path::new("/vault/Daily/2025-10-20.md")),
+            date(2025, 10, 20)
+        );
+        assert_eq!(pattern.date_from_stem("2025-10-20 notes"), None);
+        assert_eq!(pattern.date_from_stem("README"), None);
+    }
+
+    #[test]
+    fn test_logseq_patterns() {
+        assert_eq!(
+            pattern("%Y_%m_%d").date_from_stem("2025_10_20"),
+            date(2025, 10, 20)
+        );
+        assert_eq!(
+            pattern("regex:^(?P<date>[A-Z][a-z]{2} \\d{1,2}(st|nd|rd|th)?, \\d{4})$")
+                .date_from_stem("Oct 20th, 2025"),
+            date(2025, 10, 20)
+        );
+    }
+
+    #[test]
+    fn test_prefixed_and_day_first_patterns() {
+        assert_eq!(
+            pattern("journal-%Y-%m-%d").date_from_stem("journal-2025-10-20"),
+            date(2025, 10, 20)
+        );
+        assert_eq!(
+            pattern("%d-%m-%Y").date_from_stem("20-10-2025"),
+            date(2025, 10, 20)
+        );
+        assert_eq!(
+            pattern("%b %d, %Y").date_from_stem("Oct 20, 2025"),
+            date(2025, 10, 20)
+        );
+    }
+
+    #[test]
+    fn test_regex_pattern_with_date_group() {
+        let pattern = pattern("regex:(?P<date>\\d{4}-\\d{2}-\\d{2})");
+
+        assert_eq!(
+            pattern.date_from_stem("daily 2025-10-20 retro"),
+            date(2025, 10, 20)
+        );
+        assert_eq!(pattern.date_from_stem("2025-13-40"), None);
+        assert_eq!(pattern.to_string(), "regex:(?P<date>\\d{4}-\\d{2}-\\d{2})");
+    }
+
+    #[test]
+    fn test_invalid_patterns_are_rejected() {
+        assert!("regex:\\d{4}".parse::<FilenamePattern>().is_err());
+        assert!("regex:(?P<date>".parse::<FilenamePattern>().is_err());
+        assert!("%Y-%Q".parse::<FilenamePattern>().is_err());
+    }
+}
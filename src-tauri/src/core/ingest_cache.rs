@@ -0,0 +1,131 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    core::{load_ingest_config, IngestConfig},
+    db::journal_roots::{get_enabled_journal_roots, JournalRoot},
+    DbConnection,
+};
+
+type RootConfigs = Vec<(JournalRoot, Arc<IngestConfig>)>;
+
+/// The settings each enabled journal root's files are read with, loaded once for every
+/// file a sync reads rather than once per file. Commands that change how notes are
+/// read, which files are read or when, or which roots are enabled, call
+/// [`IngestConfigCache::invalidate`].
+#[derive(Default)]
+pub struct IngestConfigCache {
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Bumped by every invalidation, so a load that raced one isn't kept.
+    generation: u64,
+    roots: Option<Arc<RootConfigs>>,
+}
+
+impl IngestConfigCache {
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The settings for a file, from the enabled root it lives under, preferring the
+    /// innermost one when roots nest. Returns `None` for files outside every enabled
+    /// root.
+    pub fn for_file(
+        &self,
+        db: &DbConnection,
+        file_path: &str,
+    ) -> Result<Option<Arc<IngestConfig>>, anyhow::Error> {
+        let file_path = Path::new(file_path);
+        Ok(self
+            .roots(db)?
+            .iter()
+            .filter(|(root, _)| root.contains(file_path))
+            .max_by_key(|(root, _)| root.path.len())
+            .map(|(_, config)| Arc::clone(config)))
+    }
+
+    /// Drops the loaded settings, so the next file is read with the current ones.
+    pub fn invalidate(&self) {
+        let mut state = self.lock();
+        state.generation += 1;
+        state.roots = None;
+    }
+
+    /// Counts the invalidations so far, so settings kept elsewhere, such as the
+    /// watcher's, can tell when to reload.
+    pub fn generation(&self) -> u64 {
+        self.lock().generation
+    }
+
+    fn roots(&self, db: &DbConnection) -> Result<Arc<RootConfigs>, anyhow::Error> {
+        let generation = {
+            let state = self.lock();
+            if let Some(roots) = &state.roots {
+                return Ok(Arc::clone(roots));
+            }
+            state.generation
+        };
+
+        let roots = get_enabled_journal_roots(db)?
+            .into_iter()
+            .map(|root| {
+                let config = load_ingest_config(db, &root)?;
+                Ok((root, Arc::new(config)))
+            })
+            .collect::<Result<RootConfigs, anyhow::Error>>()?;
+        let roots = Arc::new(roots);
+
+        let mut state = self.lock();
+        if state.generation == generation {
+            state.roots = Some(Arc::clone(&roots));
+        }
+        Ok(roots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::journal_roots::upsert_journal_root;
+
+    #[test]
+    fn test_settings_are_reloaded_once_invalidated() {
+        let db = DbConnection::open_in_memory().unwrap();
+        upsert_journal_root(
+            &db,
+            &JournalRoot {
+                path: "/journal".to_string(),
+                filename_pattern: None,
+                enabled: true,
+            },
+        )
+        .unwrap();
+        let track = |name: &str| {
+            db.write()
+                .unwrap()
+                .execute("INSERT INTO tracked_metrics (value) VALUES (?1)", [name])
+                .unwrap();
+        };
+        let cache = IngestConfigCache::default();
+        let tracked = |path: &str| {
+            cache
+                .for_file(&db, path)
+                .unwrap()
+                .map(|config| config.tracked_metrics.len())
+        };
+
+        track("pages_read");
+        assert_eq!(tracked("/journal/2025-10-20.md"), Some(1));
+        assert_eq!(tracked("/elsewhere/2025-10-20.md"), None);
+
+        track("exercise");
+        assert_eq!(tracked("/journal/2025-10-20.md"), Some(1));
+        cache.invalidate();
+        assert_eq!(tracked("/journal/2025-10-20.md"), Some(2));
+    }
+}
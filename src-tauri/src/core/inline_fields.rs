@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveTime;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::core::source_format::properties::parse_time;
+
+/// Which source a metric is taken from when a note sets it both in front matter and as
+/// an inline field.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldPrecedence {
+    #[default]
+    FrontMatter,
+    Inline,
+}
+
+impl FieldPrecedence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldPrecedence::FrontMatter => "front_matter",
+            FieldPrecedence::Inline => "inline",
+        }
+    }
+}
+
+impl fmt::Display for FieldPrecedence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for FieldPrecedence {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "front_matter" => Ok(FieldPrecedence::FrontMatter),
+            "inline" => Ok(FieldPrecedence::Inline),
+            other => Err(anyhow!("Unknown field precedence {}", other)),
+        }
+    }
+}
+
+/// The value of an inline field and where it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineField {
+    pub value: String,
+    /// 1-based line within the note body.
+    pub line: usize,
+    /// The time of day the line starts with, as in `- 07:30 run_km:: 5`.
+    pub time: Option<NaiveTime>,
+}
+
+/// Dataview-style `key:: value` fields found in a note body, keyed by field name.
+/// A key that appears more than once keeps every value, in the order written.
+pub type InlineFields = HashMap<String, Vec<InlineField>>;
+
+/// A line that is a field on its own, optionally inside a list item or quote and after
+/// a time of day: `pages_read:: 20`, `- pages_read:: 20` or `- 07:30 run_km:: 5`.
+static LINE_FIELD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^\s*(?:(?:[-*+]|\d+[.)]|>)\s+)*(?:\[[ xX]\]\s+)?(?:\d{1,2}:\d{2}(?::\d{2})?\s+)?([^\[\]():]+?)::\s*(.*?)\s*$",
+    )
+    .expect("valid inline field regex")
+});
+
+/// A time of day at the start of a line, after any list marker: `- 07:30 ...`.
+static LINE_TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:(?:[-*+]|\d+[.)]|>)\s+)*(?:\[[ xX]\]\s+)?(\d{1,2}:\d{2}(?::\d{2})?)\s")
+        .expect("valid line time regex")
+});
+
+/// A field embedded in running text: `[pages_read:: 20]` or `(pages_read:: 20)`.
+static EMBEDDED_FIELD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\[([^\[\]():]+?)::\s*([^\]]*?)\s*\]|\(([^\[\]():]+?)::\s*([^)]*?)\s*\)")
+        .expect("valid embedded inline field regex")
+});
+
+/// Collects the inline fields of a note body. Fenced code blocks are skipped, so
+/// snippets that happen to contain `::` aren't read as fields.
+pub fn parse_inline_fields(body: &str) -> InlineFields {
+    let mut fields = InlineFields::new();
+
+    for (index, line) in prose_lines(body) {
+        let time = LINE_TIME
+            .captures(line)
+            .and_then(|captures| parse_time(&captures[1]));
+        let mut found_embedded = false;
+        for captures in EMBEDDED_FIELD.captures_iter(line) {
+            found_embedded = true;
+            let key = captures.get(1).or_else(|| captures.get(3));
+            let value = captures.get(2).or_else(|| captures.get(4));
+            if let (Some(key), Some(value)) = (key, value) {
+                insert_field(&mut fields, key.as_str(), value.as_str(), index + 1, time);
+            }
+        }
+        if found_embedded {
+            continue;
+        }
+
+        if let Some(captures) = LINE_FIELD.captures(line) {
+            insert_field(&mut fields, &captures[1], &captures[2], index + 1, time);
+        }
+    }
+
+    fields
+}
+
+/// The lines of a note body outside fenced code blocks, with their 0-based index.
+pub fn prose_lines(body: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut in_code_block = false;
+    body.lines().enumerate().filter(move |(_, line)| {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            return false;
+        }
+        !in_code_block
+    })
+}
+
+fn insert_field(
+    fields: &mut InlineFields,
+    key: &str,
+    value: &str,
+    line: usize,
+    time: Option<NaiveTime>,
+) {
+    // Dataview lets keys be bolded or italicised for display.
+    let key = key.trim().trim_matches(['*', '_']).trim();
+    if key.is_empty() || value.is_empty() {
+        return;
+    }
+    fields
+        .entry(key.to_string())
+        .or_default()
+        .push(InlineField {
+            value: value.to_string(),
+            line,
+            time,
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value<'a>(fields: &'a InlineFields, key: &str) -> Option<&'a str> {
+        fields.get(key)?.first().map(|field| field.value.as_str())
+    }
+
+    #[test]
+    fn test_parse_line_and_bracketed_fields() {
+        let body = "# Tuesday
+
+pages_read:: 20
+- workout:: 45m
+**mood**:: 7
+
+Finished the book [rating:: 4] and went for a walk (steps:: 8000).
+pages_read:: 99
+
+```
+not_a_field:: 1
+```
+";
+        let fields = parse_inline_fields(body);
+
+        assert_eq!(value(&fields, "pages_read"), Some("20"));
+        assert_eq!(value(&fields, "workout"), Some("45m"));
+        assert_eq!(value(&fields, "mood"), Some("7"));
+        assert_eq!(value(&fields, "rating"), Some("4"));
+        assert_eq!(value(&fields, "steps"), Some("8000"));
+        assert_eq!(fields["rating"][0].line, 7);
+        assert_eq!(fields["pages_read"][1].value, "99");
+        assert!(!fields.contains_key("not_a_field"));
+    }
+
+    #[test]
+    fn test_timed_log_lines() {
+        let fields =
+            parse_inline_fields("- 07:30 run_km:: 5\n- 18:00 went out again [run_km:: 3]\n");
+        let runs = fields["run_km"]
+            .iter()
+            .map(|field| (field.value.as_str(), field.time.map(|t| t.to_string())))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            runs,
+            vec![
+                ("5", Some("07:30:00".to_string())),
+                ("3", Some("18:00:00".to_string()))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plain_text_is_not_a_field() {
+        let fields = parse_inline_fields("Time: 10:30\nSee https://example.com\n");
+        assert!(fields.is_empty());
+    }
+}
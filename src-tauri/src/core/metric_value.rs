@@ -0,0 +1,483 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use chrono::NaiveTime;
+use rusqlite::types::{ToSql, ToSqlOutput};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::core::{checkboxes::CheckboxLabel, units::Unit};
+
+/// How a tracked metric is parsed from a note and aggregated in the dashboard.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricType {
+    #[default]
+    Integer,
+    Float,
+    Boolean,
+    /// Stored in minutes.
+    Duration,
+    /// Stored as 0-100.
+    Percentage,
+}
+
+impl MetricType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Integer => "integer",
+            MetricType::Float => "float",
+            MetricType::Boolean => "boolean",
+            MetricType::Duration => "duration",
+            MetricType::Percentage => "percentage",
+        }
+    }
+
+    /// SQL aggregate used for period totals. Percentages can't be summed, so their
+    /// "total" is the mean; booleans sum to the number of days completed.
+    pub fn total_sql(&self) -> &'static str {
+        match self {
+            MetricType::Percentage => "AVG(value)",
+            _ => "SUM(value)",
+        }
+    }
+
+    /// The unit values of this type are always stored in, whatever the metric's own.
+    pub fn implied_unit(&self) -> Option<&'static str> {
+        match self {
+            MetricType::Duration => Some("min"),
+            MetricType::Percentage => Some("%"),
+            MetricType::Integer | MetricType::Float | MetricType::Boolean => None,
+        }
+    }
+
+    /// Decimal places kept when reporting averages and totals.
+    pub fn precision(&self) -> i32 {
+        match self {
+            MetricType::Integer | MetricType::Percentage => 0,
+            MetricType::Float | MetricType::Duration | MetricType::Boolean => 2,
+        }
+    }
+
+    pub fn parse_value(&self, value: &Value) -> Result<MetricValue> {
+        match value {
+            Value::String(raw) => self.parse_str(raw),
+            Value::Bool(flag) => match self {
+                MetricType::Boolean => Ok(MetricValue::Boolean(*flag)),
+                _ => Err(anyhow!("expected a {} but found a boolean", self)),
+            },
+            Value::Number(number) => {
+                let number = number
+                    .as_f64()
+                    .ok_or_else(|| anyhow!("{} is not a valid number", number))?;
+                self.value_from_number(number)
+            }
+            Value::Tagged(tagged) => self.parse_value(&tagged.value),
+            Value::Null => Err(anyhow!("value is empty")),
+            Value::Sequence(_) | Value::Mapping(_) => {
+                Err(anyhow!("expected a {} but found a list or map", self))
+            }
+        }
+    }
+
+    pub fn parse_str(&self, raw: &str) -> Result<MetricValue> {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            return Err(anyhow!("value is empty"));
+        }
+
+        match self {
+            MetricType::Boolean => parse_bool(raw).map(MetricValue::Boolean),
+            MetricType::Duration => parse_duration_minutes(raw).map(MetricValue::Duration),
+            MetricType::Percentage => {
+                let number = raw.strip_suffix('%').unwrap_or(raw).trim();
+                let number = number
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("'{}' is not a percentage", raw))?;
+                self.value_from_number(number)
+            }
+            MetricType::Integer | MetricType::Float => {
+                let number = raw
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("'{}' is not a number", raw))?;
+                self.value_from_number(number)
+            }
+        }
+    }
+
+    /// Checks a number against the type, such as an integer having no fraction.
+    pub fn value_from_number(&self, number: f64) -> Result<MetricValue> {
+        if !number.is_finite() {
+            return Err(anyhow!("{} is not a finite number", number));
+        }
+
+        match self {
+            MetricType::Integer if number.fract() == 0.0 => Ok(MetricValue::Integer(number as i64)),
+            MetricType::Integer => Err(anyhow!("{} is not a whole number", number)),
+            MetricType::Float => Ok(MetricValue::Float(number)),
+            MetricType::Duration => Ok(MetricValue::Duration(number)),
+            MetricType::Percentage => Ok(MetricValue::Percentage(number)),
+            MetricType::Boolean if number == 0.0 || number == 1.0 => {
+                Ok(MetricValue::Boolean(number == 1.0))
+            }
+            MetricType::Boolean => Err(anyhow!("{} is not a boolean", number)),
+        }
+    }
+}
+
+impl fmt::Display for MetricType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MetricType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "integer" => Ok(MetricType::Integer),
+            "float" => Ok(MetricType::Float),
+            "boolean" => Ok(MetricType::Boolean),
+            "duration" => Ok(MetricType::Duration),
+            "percentage" => Ok(MetricType::Percentage),
+            other => Err(anyhow!("Unknown metric type {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricValue {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    /// Minutes.
+    Duration(f64),
+    Percentage(f64),
+}
+
+impl MetricValue {
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            MetricValue::Integer(value) => *value as f64,
+            MetricValue::Boolean(value) => f64::from(u8::from(*value)),
+            MetricValue::Float(value)
+            | MetricValue::Duration(value)
+            | MetricValue::Percentage(value) => *value,
+        }
+    }
+}
+
+impl ToSql for MetricValue {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            MetricValue::Integer(value) => Ok(ToSqlOutput::from(*value)),
+            MetricValue::Boolean(value) => Ok(ToSqlOutput::from(i64::from(*value))),
+            _ => Ok(ToSqlOutput::from(self.as_f64())),
+        }
+    }
+}
+
+/// A value logged for a metric, with the time of day it was logged at when the note
+/// says, as in `- 07:30 run_km:: 5`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimedValue {
+    pub value: MetricValue,
+    pub time: Option<NaiveTime>,
+}
+
+/// How the values a metric is logged with over one day combine into that day's value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    Sum,
+    Max,
+    /// The value logged latest in the day. Untimed values count as logged before timed
+    /// ones, and among equal times the one written last wins.
+    Last,
+    /// The number of values logged, whatever they are.
+    Count,
+}
+
+impl Aggregation {
+    /// The aggregation of metrics that haven't been given one: a boolean is done if it
+    /// was done at all, a percentage is its latest reading, and the rest add up.
+    pub fn default_for(metric_type: MetricType) -> Self {
+        match metric_type {
+            MetricType::Boolean => Aggregation::Max,
+            MetricType::Percentage => Aggregation::Last,
+            MetricType::Integer | MetricType::Float | MetricType::Duration => Aggregation::Sum,
+        }
+    }
+
+    /// Whether values of `metric_type` can be combined this way. Adding up or counting
+    /// booleans or percentages doesn't give a value of the same type, which streaks and
+    /// totals rely on.
+    pub fn supports(&self, metric_type: MetricType) -> bool {
+        !matches!(
+            (self, metric_type),
+            (
+                Aggregation::Sum | Aggregation::Count,
+                MetricType::Boolean | MetricType::Percentage
+            )
+        )
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Aggregation::Sum => "sum",
+            Aggregation::Max => "max",
+            Aggregation::Last => "last",
+            Aggregation::Count => "count",
+        }
+    }
+
+    /// Combines the values of one day, given in the order they were written. Sums keep
+    /// the values' type; counts are whole numbers.
+    pub fn combine(&self, values: &[TimedValue]) -> Option<MetricValue> {
+        let first = values.first()?.value;
+        let combined = match self {
+            Aggregation::Sum => {
+                let total: f64 = values.iter().map(|entry| entry.value.as_f64()).sum();
+                match first {
+                    MetricValue::Integer(_) | MetricValue::Boolean(_) => {
+                        MetricValue::Integer(total as i64)
+                    }
+                    MetricValue::Float(_) => MetricValue::Float(total),
+                    MetricValue::Duration(_) => MetricValue::Duration(total),
+                    MetricValue::Percentage(_) => MetricValue::Percentage(total),
+                }
+            }
+            Aggregation::Max => values
+                .iter()
+                .map(|entry| entry.value)
+                .max_by(|a, b| a.as_f64().total_cmp(&b.as_f64()))?,
+            Aggregation::Last => {
+                values
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(index, entry)| (entry.time, *index))?
+                    .1
+                    .value
+            }
+            Aggregation::Count => MetricValue::Integer(values.len() as i64),
+        };
+        Some(combined)
+    }
+}
+
+impl fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Aggregation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "sum" => Ok(Aggregation::Sum),
+            "max" => Ok(Aggregation::Max),
+            "last" => Ok(Aggregation::Last),
+            "count" => Ok(Aggregation::Count),
+            other => Err(anyhow!("Unknown aggregation {}", other)),
+        }
+    }
+}
+
+/// A tracked metric together with its declared type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricDefinition {
+    pub name: String,
+    pub metric_type: MetricType,
+    /// How values logged more than once on a day make up the day's value.
+    pub aggregation: Aggregation,
+    /// What integer and float values are measured in. Values logged in another unit
+    /// of the same kind are converted to it.
+    pub unit: Option<Unit>,
+    /// Other keys the metric has been logged under. Values found under an alias are
+    /// stored under `name`.
+    pub aliases: Vec<String>,
+    /// Checklist items that record this metric as a boolean completion.
+    pub checkbox_label: Option<CheckboxLabel>,
+}
+
+#[cfg(test)]
+impl MetricDefinition {
+    /// A metric read under its name alone, combined the default way for its type.
+    pub fn new(name: &str, metric_type: MetricType) -> Self {
+        MetricDefinition {
+            name: name.to_string(),
+            metric_type,
+            aggregation: Aggregation::default_for(metric_type),
+            unit: None,
+            aliases: Vec::new(),
+            checkbox_label: None,
+        }
+    }
+}
+
+impl MetricDefinition {
+    /// Parses a value written as text. A number with a unit, such as `3mi`, is
+    /// converted to the metric's unit.
+    pub fn parse_str(&self, raw: &str) -> Result<MetricValue> {
+        if let (Some(unit), MetricType::Integer | MetricType::Float) =
+            (&self.unit, self.metric_type)
+        {
+            if let Some(number) = unit.convert(raw)? {
+                return self.metric_type.value_from_number(number);
+            }
+        }
+        self.metric_type.parse_str(raw)
+    }
+
+    pub fn parse_value(&self, value: &Value) -> Result<MetricValue> {
+        match value {
+            Value::String(raw) => self.parse_str(raw),
+            Value::Tagged(tagged) => self.parse_value(&tagged.value),
+            _ => self.metric_type.parse_value(value),
+        }
+    }
+
+    /// The keys a note may use for this metric, canonical name first.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+}
+
+/// Folds the spellings `Pages-Read`, `pages_read` and `PAGES_READ` into one key.
+pub fn normalize_metric_key(key: &str) -> String {
+    key.trim().to_lowercase().replace('-', "_")
+}
+
+fn parse_bool(raw: &str) -> Result<bool> {
+    match raw.to_lowercase().as_str() {
+        "true" | "yes" | "y" | "on" | "done" | "x" | "1" => Ok(true),
+        "false" | "no" | "n" | "off" | "0" => Ok(false),
+        _ => Err(anyhow!("'{}' is not a boolean", raw)),
+    }
+}
+
+/// Parses `45`, `90m`, `1h30m`, `1.5h`, `2 hrs 10 min`, `30s` or `1:30` into minutes.
+fn parse_duration_minutes(raw: &str) -> Result<f64> {
+    let invalid = || anyhow!("'{}' is not a duration", raw);
+
+    if let Ok(minutes) = raw.parse::<f64>() {
+        return Ok(minutes);
+    }
+
+    if raw.contains(':') {
+        let parts = raw
+            .split(':')
+            .map(|part| part.trim().parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<f64>>>()?;
+        return match parts.as_slice() {
+            [hours, minutes] => Ok(hours * 60.0 + minutes),
+            [hours, minutes, seconds] => Ok(hours * 60.0 + minutes + seconds / 60.0),
+            _ => Err(invalid()),
+        };
+    }
+
+    let mut total = 0.0;
+    let mut rest = raw;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number = rest[..number_end].parse::<f64>().map_err(|_| invalid())?;
+        rest = rest[number_end..].trim_start();
+
+        let unit_end = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        let factor = match rest[..unit_end].to_lowercase().as_str() {
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60.0,
+            "m" | "min" | "mins" | "minute" | "minutes" => 1.0,
+            "s" | "sec" | "secs" | "second" | "seconds" => 1.0 / 60.0,
+            _ => return Err(invalid()),
+        };
+        total += number * factor;
+        rest = rest[unit_end..].trim_start();
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_typed_values() {
+        assert_eq!(
+            MetricType::Float.parse_str("5.2").unwrap(),
+            MetricValue::Float(5.2)
+        );
+        assert_eq!(
+            MetricType::Boolean.parse_value(&Value::Bool(true)).unwrap(),
+            MetricValue::Boolean(true)
+        );
+        assert_eq!(
+            MetricType::Percentage.parse_str("85%").unwrap(),
+            MetricValue::Percentage(85.0)
+        );
+        assert!(MetricType::Integer.parse_str("5.2").is_err());
+        assert!(MetricType::Boolean.parse_str("maybe").is_err());
+    }
+
+    #[test]
+    fn test_parse_durations() {
+        let minutes = |raw: &str| MetricType::Duration.parse_str(raw).unwrap().as_f64();
+
+        assert_eq!(minutes("1h30m"), 90.0);
+        assert_eq!(minutes("1.5h"), 90.0);
+        assert_eq!(minutes("2 hrs 10 min"), 130.0);
+        assert_eq!(minutes("1:30"), 90.0);
+        assert_eq!(minutes("45"), 45.0);
+        assert!(MetricType::Duration.parse_str("soon").is_err());
+    }
+
+    #[test]
+    fn test_aggregations() {
+        let at = |time: Option<&str>, value: i64| TimedValue {
+            value: MetricValue::Integer(value),
+            time: time.map(|time| NaiveTime::parse_from_str(time, "%H:%M").unwrap()),
+        };
+        let day = [at(Some("18:00"), 3), at(Some("07:30"), 5), at(None, 2)];
+
+        assert_eq!(
+            Aggregation::Sum.combine(&day),
+            Some(MetricValue::Integer(10))
+        );
+        assert_eq!(
+            Aggregation::Max.combine(&day),
+            Some(MetricValue::Integer(5))
+        );
+        assert_eq!(
+            Aggregation::Last.combine(&day),
+            Some(MetricValue::Integer(3))
+        );
+        assert_eq!(
+            Aggregation::Count.combine(&day),
+            Some(MetricValue::Integer(3))
+        );
+        assert_eq!(
+            Aggregation::Last.combine(&[at(None, 1), at(None, 4)]),
+            Some(MetricValue::Integer(4))
+        );
+        assert_eq!(Aggregation::Sum.combine(&[]), None);
+    }
+
+    #[test]
+    fn test_sums_only_apply_to_amounts() {
+        assert!(Aggregation::Sum.supports(MetricType::Duration));
+        assert!(!Aggregation::Sum.supports(MetricType::Boolean));
+        assert!(!Aggregation::Sum.supports(MetricType::Percentage));
+        assert!(Aggregation::Max.supports(MetricType::Boolean));
+        assert!(Aggregation::Count.supports(MetricType::Float));
+        assert!(!Aggregation::Count.supports(MetricType::Boolean));
+        assert!(!Aggregation::Count.supports(MetricType::Percentage));
+    }
+}
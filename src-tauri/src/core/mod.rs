@@ -1,47 +1,263 @@
-use serde::Serialize;
-use std::{sync::mpsc, time::Duration};
+use std::sync::mpsc;
 use tauri::{AppHandle, Emitter, Manager};
 
+pub mod change_events;
+pub mod checkboxes;
+pub mod debounce;
+pub mod derived;
 pub mod file_watcher;
+pub mod filename_pattern;
+pub mod ingest_cache;
+pub mod inline_fields;
+pub mod metric_value;
+pub mod path_filter;
+pub mod period;
 pub mod read_dailies;
 pub mod read_journal;
+pub mod reconcile;
+pub mod source_format;
+pub mod sync_queue;
 pub mod sync_worker;
+pub mod units;
 
 use read_dailies::read_dailies_dir;
 
-use crate::{core::file_watcher::WatchCommand, DbConnection};
+use crate::{
+    core::{
+        checkboxes::CheckboxLabel,
+        derived::{in_dependency_order, DerivedMetric},
+        file_watcher::WatchCommand,
+        filename_pattern::FilenamePattern,
+        ingest_cache::IngestConfigCache,
+        inline_fields::FieldPrecedence,
+        metric_value::{Aggregation, MetricDefinition, MetricType},
+        path_filter::JournalFilter,
+        source_format::SourceFormats,
+        sync_queue::{ResyncScope, SyncQueue},
+        units::Unit,
+    },
+    db::{
+        journal_roots::{get_enabled_journal_roots, JournalRoot},
+        utils::{
+            get_date_field, get_field_precedence, get_filename_pattern, get_inline_fields,
+            get_list_setting, get_normalize_metric_keys, get_prefer_date_field, get_source_formats,
+            EXCLUDE_GLOBS_SETTING, INCLUDE_GLOBS_SETTING,
+        },
+    },
+    DbConnection,
+};
 
-fn get_tracked_metrics_from_db(db: &DbConnection) -> Result<Vec<String>, anyhow::Error> {
-    let conn = db
-        .lock()
-        .map_err(|e| anyhow::anyhow!("Failed to lock connection: {}", e))?;
+/// Everything the sync worker needs to turn a journal file into metrics.
+#[derive(Default)]
+pub struct IngestConfig {
+    pub tracked_metrics: Vec<MetricDefinition>,
+    /// Metrics computed from the tracked ones, in the order they are evaluated.
+    pub derived_metrics: Vec<DerivedMetric>,
+    pub formats: SourceFormats,
+    pub filename_pattern: FilenamePattern,
+    /// Front matter key holding the entry date, for notes not named after their date.
+    pub date_field: Option<String>,
+    /// Take the date from `date_field` even when the file name has one.
+    pub prefer_date_field: bool,
+    /// Also read Dataview-style `key:: value` fields from the note body.
+    pub inline_fields: bool,
+    pub field_precedence: FieldPrecedence,
+    /// Match metric keys ignoring case and treating `-` and `_` as the same.
+    pub normalize_keys: bool,
+}
+
+/// Loads the settings for the files of one journal root, using its filename pattern.
+pub fn load_ingest_config(
+    db: &DbConnection,
+    root: &JournalRoot,
+) -> Result<IngestConfig, anyhow::Error> {
+    Ok(IngestConfig {
+        tracked_metrics: get_tracked_metrics_from_db(db)?,
+        derived_metrics: get_derived_metrics_from_db(db)?,
+        formats: get_source_formats(db)?,
+        filename_pattern: root_filename_pattern(db, root)?,
+        date_field: get_date_field(db)?,
+        prefer_date_field: get_prefer_date_field(db)?,
+        inline_fields: get_inline_fields(db)?,
+        field_precedence: get_field_precedence(db)?,
+        normalize_keys: get_normalize_metric_keys(db)?,
+    })
+}
+
+/// The root's own filename pattern, or the global one when it has none.
+pub fn root_filename_pattern(
+    db: &DbConnection,
+    root: &JournalRoot,
+) -> Result<FilenamePattern, anyhow::Error> {
+    match &root.filename_pattern {
+        Some(pattern) => pattern.parse(),
+        None => get_filename_pattern(db),
+    }
+}
+
+/// Builds the include/exclude filter shared by the initial scan and the watcher.
+pub fn load_journal_filter(db: &DbConnection, root: &str) -> Result<JournalFilter, anyhow::Error> {
+    Ok(JournalFilter::new(
+        root,
+        &get_list_setting(db, INCLUDE_GLOBS_SETTING)?,
+        &get_list_setting(db, EXCLUDE_GLOBS_SETTING)?,
+    )?
+    .with_formats(get_source_formats(db)?))
+}
+
+fn get_tracked_metrics_from_db(db: &DbConnection) -> Result<Vec<MetricDefinition>, anyhow::Error> {
+    let conn = db.read()?;
 
-    let mut stmt = conn.prepare("SELECT value FROM tracked_metrics")?;
-    let metric_iter = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let mut stmt = conn.prepare(
+        "SELECT value, metric_type, aliases, checkbox_label, aggregation, unit FROM tracked_metrics WHERE formula IS NULL",
+    )?;
+    let metric_iter = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+        ))
+    })?;
 
     let mut metrics = Vec::new();
     for metric in metric_iter {
-        metrics.push(metric?);
+        let (name, metric_type, aliases, checkbox_label, aggregation, unit) = metric?;
+        let metric_type = metric_type.parse::<MetricType>().unwrap_or_else(|e| {
+            eprintln!("{} for metric {}, treating it as an integer", e, name);
+            MetricType::default()
+        });
+        // Aggregations stored before a type stopped supporting them fall back too.
+        let aggregation = parse_aggregation(aggregation.as_deref(), metric_type)
+            .and_then(|aggregation| match aggregation.supports(metric_type) {
+                true => Ok(aggregation),
+                false => Err(anyhow::anyhow!(
+                    "{} metrics can't be combined with {}",
+                    metric_type,
+                    aggregation
+                )),
+            })
+            .unwrap_or_else(|e| {
+                eprintln!("{} for metric {}, using its default", e, name);
+                Aggregation::default_for(metric_type)
+            });
+        let aliases = parse_aliases(aliases.as_deref()).unwrap_or_else(|e| {
+            eprintln!("{} for metric {}, ignoring its aliases", e, name);
+            Vec::new()
+        });
+        let checkbox_label = checkbox_label.and_then(|label| {
+            label
+                .parse::<CheckboxLabel>()
+                .map_err(|e| eprintln!("{} for metric {}, ignoring checkboxes", e, name))
+                .ok()
+        });
+        let unit = unit.and_then(|unit| {
+            unit.parse::<Unit>()
+                .map_err(|e| eprintln!("{} for metric {}, ignoring its unit", e, name))
+                .ok()
+        });
+        metrics.push(MetricDefinition {
+            name,
+            metric_type,
+            aggregation,
+            aliases,
+            checkbox_label,
+            unit,
+        });
     }
 
     Ok(metrics)
 }
 
-#[derive(Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct SyncProgress {
-    sync_progress: f32,
+/// Loads the metrics defined by a formula, ready to evaluate in order. Formulas that no
+/// longer parse, or that depend on each other, are left out.
+pub fn get_derived_metrics_from_db(db: &DbConnection) -> Result<Vec<DerivedMetric>, anyhow::Error> {
+    let conn = db.read()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT value, metric_type, formula FROM tracked_metrics WHERE formula IS NOT NULL",
+    )?;
+    let metric_iter = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+
+    let mut derived = Vec::new();
+    for metric in metric_iter {
+        let (name, metric_type, formula) = metric?;
+        let metric_type = metric_type
+            .parse::<MetricType>()
+            .unwrap_or(MetricType::Float);
+        match DerivedMetric::new(&name, &formula, metric_type) {
+            Ok(metric) => derived.push(metric),
+            Err(e) => eprintln!("{} for metric {}, skipping it", e, name),
+        }
+    }
+
+    in_dependency_order(derived).or_else(|e| {
+        eprintln!("{}, skipping derived metrics", e);
+        Ok(Vec::new())
+    })
+}
+
+/// Reads the aggregation column of `tracked_metrics`, which is empty for metrics that
+/// use their type's default.
+pub fn parse_aggregation(
+    aggregation: Option<&str>,
+    metric_type: MetricType,
+) -> Result<Aggregation, anyhow::Error> {
+    match aggregation {
+        Some(aggregation) => aggregation.parse(),
+        None => Ok(Aggregation::default_for(metric_type)),
+    }
+}
+
+/// Reads the aliases column of `tracked_metrics`, a JSON array of strings.
+pub fn parse_aliases(aliases: Option<&str>) -> Result<Vec<String>, anyhow::Error> {
+    match aliases {
+        Some(aliases) => serde_json::from_str(aliases)
+            .map_err(|e| anyhow::anyhow!("Failed to parse aliases {}: {}", aliases, e)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Re-reads every enabled journal root.
+pub async fn resync_database(app_handle: AppHandle) -> Result<(), anyhow::Error> {
+    let roots = {
+        let db = app_handle.state::<DbConnection>();
+        get_enabled_journal_roots(&db)?
+    };
+    resync(app_handle, ResyncScope::All, roots).await
+}
+
+/// Re-reads the given roots, alongside any resync of other roots.
+pub async fn resync_journal_roots(
+    app_handle: AppHandle,
+    roots: Vec<JournalRoot>,
+) -> Result<(), anyhow::Error> {
+    let scope = ResyncScope::roots(roots.iter().map(|root| root.path.clone()).collect());
+    resync(app_handle, scope, roots).await
 }
 
-pub async fn resync_database(
+/// Queues every file under `roots` for the sync workers and waits until they are read.
+/// Starting a resync of the same scope, or a full one, meanwhile cancels this one,
+/// leaving the new one to finish. Resyncs follow settings changes, so the workers'
+/// cached settings are dropped first.
+async fn resync(
     app_handle: AppHandle,
-    journal_path: &str,
+    scope: ResyncScope,
+    roots: Vec<JournalRoot>,
 ) -> Result<(), anyhow::Error> {
+    app_handle.state::<IngestConfigCache>().invalidate();
     let db = app_handle.state::<DbConnection>();
-    let tx = app_handle.state::<mpsc::SyncSender<String>>().clone();
     let tracked_metrics = get_tracked_metrics_from_db(&db)?;
 
-    if tracked_metrics.is_empty() {
+    if tracked_metrics.is_empty() || roots.is_empty() {
         return Ok(());
     }
 
@@ -50,82 +266,59 @@ pub async fn resync_database(
         Err(e) => println!("Failed to emit sync-start event: {}", e),
     }
 
-    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel::<f32>(100);
     let app_handle_clone = app_handle.clone();
-
-    tauri::async_runtime::spawn(async move {
-        let mut latest: Option<f32> = None;
-        let mut ticker = tokio::time::interval(Duration::from_millis(500));
-        loop {
-            tokio::select! {
-                progress = progress_rx.recv() => {
-                    match progress {
-                        Some(p) => {
-                            latest = Some(p);
-                        }
-                        None => {
-                            // Channel closed - send final progress and break
-                            println!("Progress channel closed, ending progress emitter");
-                            let _ = app_handle_clone.emit("sync-complete", ());
-                            break;
-                        }
-                    }
-                }
-                _ = ticker.tick() => {
-                    if let Some(progress) = latest.take() {
-                        match app_handle_clone.emit("sync-progress", SyncProgress { sync_progress: progress }) {
-                            Ok(_) => println!("Emitted progress: {:.2}%", progress),
-                            Err(e) => eprintln!("Failed to emit progress event: {}", e),
-                        }
-                    }
-                }
-            }
+    let completed = tauri::async_runtime::spawn_blocking(move || {
+        let mut file_paths = Vec::new();
+        for root in roots {
+            file_paths.extend(read_dailies_dir(&root, app_handle_clone.clone())?);
         }
-    });
+        let queue = app_handle_clone.state::<SyncQueue>();
+        let id = queue.start_resync(scope, file_paths);
+        Ok::<_, anyhow::Error>(queue.wait_for_resync(id))
+    })
+    .await??;
 
-    let app_handle_clone = app_handle.clone();
-    let jouornal_path_clone = journal_path.to_string();
-    let handle = tauri::async_runtime::spawn_blocking(move || {
-        read_dailies_dir(jouornal_path_clone, app_handle_clone)
-    });
-
-    let file_paths = handle.await??;
-    let len = file_paths.len();
-    for (i, path) in file_paths.into_iter().enumerate() {
-        let progress = ((i + 1) as f32 / len as f32) * 100.0;
-        if tx.send(path).is_ok() {
-            let _ = progress_tx.send(progress.round()).await;
+    // A superseded resync leaves reporting completion to the one that replaced it.
+    match completed {
+        Some(status) => {
+            if status.failed > 0 {
+                println!("Resync finished with {} unreadable files", status.failed);
+            }
+            let _ = app_handle.emit("sync-complete", ());
         }
+        None => println!("Resync superseded by a newer one"),
     }
 
-    drop(progress_tx);
-
     Ok(())
 }
 
 pub fn init(
     app_handle: AppHandle,
-    journal_path: Option<String>,
+    journal_roots: Vec<JournalRoot>,
 ) -> Result<mpsc::Sender<WatchCommand>, anyhow::Error> {
     let db = app_handle.state::<DbConnection>();
 
     let tracked_metrics = get_tracked_metrics_from_db(&db)?;
-    let tx = app_handle.state::<mpsc::SyncSender<String>>().clone();
-
-    if let Some(root_dir) = &journal_path {
-        if !tracked_metrics.is_empty() {
-            let file_paths = read_dailies_dir(root_dir.clone(), app_handle.clone())?;
-            for path in file_paths {
-                match tx.clone().send(path) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        eprintln!("Failed to send file path to sync worker: {}", e);
-                    }
-                }
+
+    // Catch up on what changed while the app was closed, reading only those files.
+    if !tracked_metrics.is_empty() {
+        match reconcile::reconcile_journal(&app_handle) {
+            Ok(reconciliation) => {
+                println!(
+                    "Startup scan: {} changed, {} removed, {} unchanged",
+                    reconciliation.changed.len(),
+                    reconciliation.removed.paths.len(),
+                    reconciliation.unchanged
+                );
+                app_handle
+                    .state::<SyncQueue>()
+                    .enqueue(reconciliation.changed);
             }
+            Err(e) => eprintln!("Failed to reconcile journal at startup: {}", e),
         }
-    };
+    }
 
-    let watcher = file_watcher::start_watcher(app_handle, journal_path)?;
+    let watch_paths = journal_roots.into_iter().map(|root| root.path).collect();
+    let watcher = file_watcher::start_watcher(app_handle, watch_paths)?;
     Ok(watcher)
 }
@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::core::source_format::SourceFormats;
+
+/// Decides which files under a journal root are journal entries.
+///
+/// Globs are matched against the path relative to the root, using `/` as the
+/// separator. A glob naming a folder (`templates`) also matches everything inside it.
+/// Only files in an enabled format are considered. With no include globs every such
+/// file is included; exclude globs always win.
+#[derive(Debug, Clone)]
+pub struct JournalFilter {
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    formats: SourceFormats,
+}
+
+impl JournalFilter {
+    pub fn new(root: impl Into<PathBuf>, include: &[String], exclude: &[String]) -> Result<Self> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(include)?)
+        };
+
+        Ok(JournalFilter {
+            root: root.into(),
+            include,
+            exclude: build_glob_set(exclude)?,
+            formats: SourceFormats::default(),
+        })
+    }
+
+    /// Reads files in `formats` instead of only Markdown.
+    pub fn with_formats(mut self, formats: SourceFormats) -> Self {
+        self.formats = formats;
+        self
+    }
+
+    /// Whether `path` is a file in an enabled format under the root that passes the globs.
+    pub fn matches(&self, path: &Path) -> bool {
+        if self.formats.for_path(path).is_none() {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        if self.exclude.is_match(&relative) {
+            return false;
+        }
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(&relative))
+    }
+
+    /// Whether a directory can hold matching files, so excluded folders are skipped
+    /// while walking instead of being read and filtered file by file.
+    pub fn should_descend(&self, dir: &Path) -> bool {
+        match dir.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => true,
+            Ok(relative) => {
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                !self.exclude.is_match(format!("{}/", relative))
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+pub fn validate_globs(globs: &[String]) -> Result<()> {
+    build_glob_set(globs).map(|_| ())
+}
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        let glob = glob.trim().trim_start_matches("./").trim_end_matches('/');
+        if glob.is_empty() {
+            continue;
+        }
+        builder.add(build_glob(glob)?);
+        builder.add(build_glob(&format!("{}/**", glob))?);
+    }
+    builder
+        .build()
+        .map_err(|e| anyhow!("Failed to build glob set: {}", e))
+}
+
+fn build_glob(glob: &str) -> Result<Glob> {
+    GlobBuilder::new(glob)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| anyhow!("Invalid glob {}: {}", glob, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(globs: &[&str]) -> Vec<String> {
+        globs.iter().map(|glob| glob.to_string()).collect()
+    }
+
+    #[test]
+    fn test_excluded_folders_are_skipped() {
+        let filter =
+            JournalFilter::new("/vault", &[], &globs(&["templates", ".trash/**"])).unwrap();
+
+        assert!(filter.matches(Path::new("/vault/Daily/2025/10/2025-10-20.md")));
+        assert!(!filter.matches(Path::new("/vault/templates/daily.md")));
+        assert!(!filter.matches(Path::new("/vault/.trash/2025-10-20.md")));
+        assert!(!filter.matches(Path::new("/vault/Daily/2025-10-20.txt")));
+        assert!(!filter.matches(Path::new("/elsewhere/2025-10-20.md")));
+
+        assert!(filter.should_descend(Path::new("/vault")));
+        assert!(filter.should_descend(Path::new("/vault/Daily")));
+        assert!(!filter.should_descend(Path::new("/vault/templates")));
+        assert!(!filter.should_descend(Path::new("/vault/.trash")));
+    }
+
+    #[test]
+    fn test_include_globs_limit_matches() {
+        let filter = JournalFilter::new("/vault", &globs(&["Daily/**/*.md"]), &[]).unwrap();
+
+        assert!(filter.matches(Path::new("/vault/Daily/2025/10/2025-10-20.md")));
+        assert!(!filter.matches(Path::new("/vault/Projects/2025-10-20.md")));
+    }
+
+    #[test]
+    fn test_invalid_glob_is_rejected() {
+        assert!(validate_globs(&globs(&["Daily/[2025"])).is_err());
+    }
+}
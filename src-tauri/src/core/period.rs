@@ -0,0 +1,160 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// How much time a journal entry, and the metrics read from it, covers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl Granularity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Granularity::Day => "day",
+            Granularity::Week => "week",
+            Granularity::Month => "month",
+        }
+    }
+}
+
+impl fmt::Display for Granularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Granularity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "day" => Ok(Granularity::Day),
+            "week" => Ok(Granularity::Week),
+            "month" => Ok(Granularity::Month),
+            other => Err(anyhow!("Unknown period granularity {}", other)),
+        }
+    }
+}
+
+/// The day, ISO week or calendar month a journal entry belongs to. Metrics are stored
+/// against the period's first day along with its granularity, so weekly and monthly
+/// values are never mistaken for the value of that one day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Period {
+    pub granularity: Granularity,
+    pub start: NaiveDate,
+}
+
+impl Period {
+    pub fn day(date: NaiveDate) -> Self {
+        Period {
+            granularity: Granularity::Day,
+            start: date,
+        }
+    }
+
+    /// The ISO week, Monday to Sunday, that `date` falls in.
+    pub fn week_of(date: NaiveDate) -> Self {
+        Period {
+            granularity: Granularity::Week,
+            start: date.week(Weekday::Mon).first_day(),
+        }
+    }
+
+    pub fn month_of(date: NaiveDate) -> Self {
+        Period {
+            granularity: Granularity::Month,
+            start: date.with_day(1).unwrap_or(date),
+        }
+    }
+
+    /// Reads the file stem of a weekly (`2025-W42`) or monthly (`2025-10`) note, as
+    /// named by the Periodic Notes plugin's defaults.
+    pub fn from_stem(stem: &str) -> Option<Self> {
+        let (year, rest) = stem.split_once('-')?;
+        if year.len() != 4 {
+            return None;
+        }
+        let year = year.parse::<i32>().ok()?;
+
+        if let Some(week) = rest.strip_prefix(['W', 'w']) {
+            if week.len() != 2 {
+                return None;
+            }
+            let start = NaiveDate::from_isoywd_opt(year, week.parse().ok()?, Weekday::Mon)?;
+            return Some(Period::week_of(start));
+        }
+
+        if rest.len() != 2 {
+            return None;
+        }
+        let start = NaiveDate::from_ymd_opt(year, rest.parse().ok()?, 1)?;
+        Some(Period::month_of(start))
+    }
+
+    /// The last day of the period.
+    pub fn end(&self) -> NaiveDate {
+        match self.granularity {
+            Granularity::Day => self.start,
+            Granularity::Week => self.start + Days::new(6),
+            Granularity::Month => self
+                .start
+                .checked_add_months(Months::new(1))
+                .and_then(|next| next.pred_opt())
+                .unwrap_or(self.start),
+        }
+    }
+
+    /// How the period is written in note names: `2025-10-20`, `2025-W42` or `2025-10`.
+    pub fn label(&self) -> String {
+        match self.granularity {
+            Granularity::Day => self.start.format("%Y-%m-%d").to_string(),
+            Granularity::Week => {
+                let week = self.start.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Granularity::Month => self.start.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_periods_from_note_names() {
+        let week = Period::from_stem("2025-W42").unwrap();
+        assert_eq!(week.granularity, Granularity::Week);
+        assert_eq!(week.start, date(2025, 10, 13));
+        assert_eq!(week.end(), date(2025, 10, 19));
+        assert_eq!(week.label(), "2025-W42");
+
+        let month = Period::from_stem("2024-02").unwrap();
+        assert_eq!(month.granularity, Granularity::Month);
+        assert_eq!(month.end(), date(2024, 2, 29));
+        assert_eq!(month.label(), "2024-02");
+
+        // ISO week 1 of 2026 starts in December 2025.
+        assert_eq!(
+            Period::from_stem("2026-W01").unwrap().start,
+            date(2025, 12, 29)
+        );
+        assert_eq!(Period::from_stem("2025-W54"), None);
+        assert_eq!(Period::from_stem("2025-13"), None);
+        assert_eq!(Period::from_stem("2025-10-20"), None);
+        assert_eq!(Period::from_stem("Retro"), None);
+    }
+}
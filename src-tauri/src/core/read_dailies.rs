@@ -1,61 +1,66 @@
 use anyhow::Context;
-use chrono::{DateTime, Utc};
-use rusqlite::params;
-use std::fs;
 use tauri::{AppHandle, Manager};
+use walkdir::{DirEntry, WalkDir};
 
-use crate::DbConnection;
+use crate::{
+    core::{
+        filename_pattern::FilenamePattern, load_journal_filter, period::Period,
+        root_filename_pattern,
+    },
+    db::{journal_roots::JournalRoot, utils::get_date_field},
+    DbConnection,
+};
 
 pub fn read_dailies_dir(
-    dir_path: String,
+    root: &JournalRoot,
     app_handle: AppHandle,
 ) -> Result<Vec<String>, anyhow::Error> {
-    let dir_entries = fs::read_dir(dir_path).with_context(|| "Failed to read the directory")?;
-
     let mut file_paths = Vec::new();
     let db = app_handle.state::<DbConnection>();
+    let filter = load_journal_filter(&db, &root.path)?;
+    let filename_pattern = root_filename_pattern(&db, root)?;
+    // Any note may carry its date in front matter, so none can be ruled out by name.
+    let has_date_field = get_date_field(&db)?.is_some();
+
+    let walker = WalkDir::new(&root.path)
+        .into_iter()
+        .filter_entry(|entry| !entry.file_type().is_dir() || filter.should_descend(entry.path()));
 
-    for entry in dir_entries {
-        let entry = entry?;
-        if let Some(path) = read_entry(entry, &db)? {
-            file_paths.push(path);
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.depth() == 0 => {
+                return Err(e).with_context(|| "Failed to read the directory");
+            }
+            Err(e) => {
+                eprintln!("Skipping unreadable journal entry: {}", e);
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() || !filter.matches(entry.path()) {
+            continue;
+        }
+        if is_entry_candidate(&entry, &filename_pattern, has_date_field) {
+            file_paths.push(entry.path().to_string_lossy().to_string());
         }
     }
 
     Ok(file_paths)
 }
 
-fn read_entry(dir_entry: fs::DirEntry, db: &DbConnection) -> Result<Option<String>, anyhow::Error> {
+/// Whether a file can be a journal entry: it is named after a date, week or month, or
+/// a date field is configured that any note may set.
+fn is_entry_candidate(
+    dir_entry: &DirEntry,
+    filename_pattern: &FilenamePattern,
+    has_date_field: bool,
+) -> bool {
     let path = dir_entry.path();
-    if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md") {
-        if let Some(file_name) = path.file_name().and_then(|s| s.to_str()) {
-            if file_name.starts_with("202") {
-                let metadata = dir_entry.metadata().with_context(|| {
-                    format!("Failed to get metadata for file: {}", path.display())
-                })?;
-                let metadata = metadata.modified().with_context(|| {
-                    format!(
-                        "Failed to get last modified time for file: {}",
-                        path.display()
-                    )
-                })?;
-                let datetime: DateTime<Utc> = metadata.into();
-                let formatted_time = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
-
-                db.lock().unwrap().execute(
-                    "INSERT OR REPLACE INTO file_meta (file_path, last_modified) VALUES (?1, ?2)",
-                    params![path.to_string_lossy().to_string(), formatted_time],
-                )
-                .with_context(|| {
-                    format!(
-                        "Failed to insert or update file metadata for {}",
-                        path.display()
-                    )
-                })?;
-
-                return Ok(Some(path.to_string_lossy().to_string()));
-            }
-        }
-    }
-    Ok(None)
+    has_date_field
+        || filename_pattern.date_from_path(path).is_some()
+        || path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(Period::from_stem)
+            .is_some()
 }
@@ -1,150 +1,1125 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Local, NaiveDate, Utc};
-use rusqlite::params;
-use std::fs::{metadata, File};
-use std::io::{BufRead, BufReader};
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::fs;
 use std::path::Path;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use crate::core::{
+    change_events::{FileIngested, MetricChanges},
+    derived::derive_values,
+    metric_value::{MetricDefinition, MetricValue, TimedValue},
+    period::Period,
+    source_format::{FoundValue, SourceEntry},
+    IngestConfig,
+};
+use crate::db::{
+    diagnostics::{replace_file_diagnostics, IngestDiagnostic},
+    utils::add_stored_metrics,
+};
 use crate::DbConnection;
 
 pub const DB_DATE_FORMAT: &str = "%Y-%m-%d";
 pub const DB_DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+pub const DB_TIME_FORMAT: &str = "%H:%M:%S";
 
+/// A metric's value for the day, week or month of an entry.
 #[derive(Debug)]
 pub struct Metric {
     pub name: String,
-    pub value: u32,
+    pub value: MetricValue,
     pub date: NaiveDate,
     pub file_path: String,
 }
 
-pub fn read_front_matter(
+/// One value logged for a metric, such as a single `- 07:30 run_km:: 5` line. An entry
+/// can log a metric many times; its aggregation combines them into the [`Metric`].
+#[derive(Debug)]
+pub struct MetricEvent {
+    pub name: String,
+    pub value: TimedValue,
+    pub line: Option<usize>,
+}
+
+/// Reads a journal file, if it changed since it was last read, and stores what it
+/// contains. The sync worker stores files in batches instead.
+#[cfg(test)]
+pub fn read_journal_file(
     path: &str,
-    needed_metrics: &Vec<String>,
+    config: &IngestConfig,
     db: &DbConnection,
 ) -> Result<(), anyhow::Error> {
-    if !should_read_file(path, db)? {
-        return Ok(());
-    }
-
-    let file = File::open(path)?;
-
-    let reader = BufReader::new(file);
-    let lines = reader.lines();
-    let mut in_front_matter = false;
-    for line in lines {
-        let line = line?;
-        if line.trim().contains("---") {
-            if in_front_matter {
-                break;
-            } else {
-                in_front_matter = true;
-                continue;
-            }
+    match parse_journal_file(path, config, db) {
+        Ok(update) => write_file_updates(db, update.as_slice()).map(|_| ()),
+        Err(e) => {
+            write_file_updates(db, &[FileUpdate::unreadable(path, &e)])?;
+            Err(e)
+        }
+    }
+}
+
+/// What reading a journal file found, for [`write_file_updates`] to store.
+pub struct FileUpdate {
+    pub path: String,
+    change: FileChange,
+}
+
+enum FileChange {
+    /// The content is what was last read; only the file's stamp moved.
+    Restamped(FileStamp),
+    /// The file was read. `entry` is missing when it couldn't be ingested, and the
+    /// diagnostics say why; the file's stored values are then dropped, since it no
+    /// longer holds any that can be read.
+    Read {
+        entry: Option<ParsedEntry>,
+        diagnostics: Vec<IngestDiagnostic>,
+    },
+}
+
+/// The rows a journal entry is stored as.
+struct ParsedEntry {
+    metrics: Vec<Metric>,
+    events: Vec<MetricEvent>,
+    stamp: FileStamp,
+    period: Period,
+    date_source: DateSource,
+}
+
+impl FileUpdate {
+    /// Replaces whatever problems were stored for the file with `error`, and drops its
+    /// metrics.
+    pub fn unreadable(path: &str, error: &anyhow::Error) -> Self {
+        FileUpdate {
+            path: path.to_string(),
+            change: FileChange::Read {
+                entry: None,
+                diagnostics: vec![IngestDiagnostic::for_file(path, error.to_string())],
+            },
         }
-        for metric in needed_metrics {
-            if line.starts_with(metric) {
-                let metric = extract_metric(&line, path)?;
-                write_metric_to_db(metric, db)?;
+    }
+
+    /// What the file's metrics are stored as once written, for files that were read
+    /// into an entry.
+    pub fn ingested(&self) -> Option<FileIngested> {
+        let FileChange::Read {
+            entry: Some(entry), ..
+        } = &self.change
+        else {
+            return None;
+        };
+        Some(FileIngested {
+            file_path: self.path.clone(),
+            date: entry.period.start.format(DB_DATE_FORMAT).to_string(),
+            metrics: entry
+                .metrics
+                .iter()
+                .map(|metric| metric.name.clone())
+                .collect(),
+        })
+    }
+}
+
+/// Parses a journal file if it changed since it was last read. The database is only
+/// read, so files can be parsed in parallel while one writer stores the results.
+pub fn parse_journal_file(
+    path: &str,
+    config: &IngestConfig,
+    db: &DbConnection,
+) -> Result<Option<FileUpdate>, anyhow::Error> {
+    let change = match read_if_changed(path, db)? {
+        OnDisk::Unchanged => return Ok(None),
+        OnDisk::Touched(stamp) => FileChange::Restamped(stamp),
+        OnDisk::Changed(file) => {
+            let mut diagnostics = Vec::new();
+            let entry = parse_entry(path, file, config, &mut diagnostics);
+            FileChange::Read { entry, diagnostics }
+        }
+    };
+    Ok(Some(FileUpdate {
+        path: path.to_string(),
+        change,
+    }))
+}
+
+/// Turns one journal file into rows, adding any problems with its content to
+/// `diagnostics`. A file that doesn't parse or whose date can't be worked out has no
+/// entry, so its stored values are dropped and it is left out of `file_meta` to be
+/// read again on the next sync.
+fn parse_entry(
+    path: &str,
+    file: ChangedFile,
+    config: &IngestConfig,
+    diagnostics: &mut Vec<IngestDiagnostic>,
+) -> Option<ParsedEntry> {
+    let content = match std::str::from_utf8(&file.content) {
+        Ok(content) => content,
+        Err(e) => {
+            diagnostics.push(IngestDiagnostic::for_file(
+                path,
+                format!("Failed to read the file: {}", e),
+            ));
+            return None;
+        }
+    };
+    let Some(format) = config.formats.for_path(Path::new(path)) else {
+        diagnostics.push(IngestDiagnostic::for_file(
+            path,
+            "No enabled journal format reads this kind of file",
+        ));
+        return None;
+    };
+    let entry = match format.parse(content, config) {
+        Ok(entry) => entry,
+        Err(e) => {
+            diagnostics.push(IngestDiagnostic {
+                line: e.line,
+                ..IngestDiagnostic::for_file(path, e.message)
+            });
+            return None;
+        }
+    };
+
+    let Some((period, date_source)) = entry_period(path, entry.as_ref(), config) else {
+        let reason = match &config.date_field {
+            Some(field) => format!(
+                "The file name does not match the pattern {} and it has no {} field",
+                config.filename_pattern, field
+            ),
+            None => format!(
+                "The file name does not match the pattern {}",
+                config.filename_pattern
+            ),
+        };
+        diagnostics.push(IngestDiagnostic::for_file(path, reason));
+        return None;
+    };
+
+    let mut metrics = Vec::new();
+    let mut events = Vec::new();
+    for metric in &config.tracked_metrics {
+        let mut values = Vec::new();
+        for found in metric_values(metric, entry.as_ref(), config) {
+            match found.value {
+                Ok(value) => {
+                    let value = TimedValue {
+                        value,
+                        time: found.time,
+                    };
+                    values.push(value);
+                    events.push(MetricEvent {
+                        name: metric.name.clone(),
+                        value,
+                        line: found.line,
+                    });
+                }
+                Err(e) => diagnostics.push(IngestDiagnostic {
+                    file_path: path.to_string(),
+                    line: found.line,
+                    metric: Some(metric.name.clone()),
+                    raw: Some(found.raw),
+                    reason: e.to_string(),
+                }),
             }
         }
+        if let Some(value) = metric.aggregation.combine(&values) {
+            metrics.push(Metric {
+                name: metric.name.clone(),
+                value,
+                date: period.start,
+                file_path: path.to_string(),
+            });
+        }
     }
-    update_file_metadata(path, db)?;
-    Ok(())
+
+    let mut values = metrics
+        .iter()
+        .map(|metric| (metric.name.clone(), metric.value.as_f64()))
+        .collect();
+    for (derived, value) in derive_values(&config.derived_metrics, &mut values) {
+        match value {
+            Ok(value) => metrics.push(Metric {
+                name: derived.name.clone(),
+                value,
+                date: period.start,
+                file_path: path.to_string(),
+            }),
+            Err(e) => diagnostics.push(IngestDiagnostic {
+                file_path: path.to_string(),
+                line: None,
+                metric: Some(derived.name.clone()),
+                raw: Some(derived.formula.clone()),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    Some(ParsedEntry {
+        metrics,
+        events,
+        stamp: file.stamp,
+        period,
+        date_source,
+    })
 }
 
-fn extract_metric(line: &str, path: &str) -> Result<Metric> {
-    let raw_date_string = Path::new(path)
-        .file_stem()
-        .and_then(|name| name.to_str())
-        .ok_or_else(|| anyhow::anyhow!("Failed to get file name from path"))?
-        .to_string();
-    let date = NaiveDate::parse_from_str(&raw_date_string, DB_DATE_FORMAT).with_context(|| {
-        format!(
-            "Failed to parse the date from the file name {}",
-            raw_date_string,
-        )
-    })?;
-
-    let parts: Vec<&str> = line.splitn(2, ':').collect();
-    if parts.len() == 2 {
-        let name = parts[0].trim().to_string();
-        let value = parts[1].trim().parse::<u32>().unwrap_or(0);
-        Ok(Metric {
-            name,
-            value,
-            date,
-            file_path: path.to_string(),
+/// Parses a tracked metric from the entry's own fields, falling back to its checklist
+/// when none sets a value. Empty means the entry doesn't mention the metric.
+fn metric_values(
+    metric: &MetricDefinition,
+    entry: &dyn SourceEntry,
+    config: &IngestConfig,
+) -> Vec<FoundValue> {
+    let found = entry.field_values(metric, config);
+    if !found.is_empty() {
+        return found;
+    }
+
+    metric
+        .checkbox_label
+        .as_ref()
+        .and_then(|label| label.completion(entry.checkboxes()))
+        .map(|done| FoundValue {
+            value: Ok(MetricValue::Boolean(done)),
+            raw: done.to_string(),
+            line: None,
+            time: None,
         })
+        .into_iter()
+        .collect()
+}
+
+/// Where the date of a journal entry was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateSource {
+    FileName,
+    /// The entry's date field: front matter, an Org property or a JSON key.
+    FrontMatter,
+}
+
+impl DateSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateSource::FileName => "file_name",
+            DateSource::FrontMatter => "front_matter",
+        }
+    }
+}
+
+/// Resolves the period an entry covers. Weekly (`2025-W42`) and monthly (`2025-10`)
+/// notes are told apart by their file name; every other entry covers one day.
+pub fn entry_period(
+    path: &str,
+    entry: &dyn SourceEntry,
+    config: &IngestConfig,
+) -> Option<(Period, DateSource)> {
+    let periodic = Path::new(path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(Period::from_stem);
+    match periodic {
+        Some(period) => Some((period, DateSource::FileName)),
+        None => entry_date(path, entry, config).map(|(date, source)| (Period::day(date), source)),
+    }
+}
+
+/// Resolves the date an entry belongs to from its file name and, when configured,
+/// the entry's date field. The file name wins unless `prefer_date_field` is set.
+pub fn entry_date(
+    path: &str,
+    entry: &dyn SourceEntry,
+    config: &IngestConfig,
+) -> Option<(NaiveDate, DateSource)> {
+    let from_file_name = || {
+        config
+            .filename_pattern
+            .date_from_path(Path::new(path))
+            .map(|date| (date, DateSource::FileName))
+    };
+    let from_date_field = || {
+        let field = config.date_field.as_deref()?;
+        entry
+            .date_field(field)
+            .map(|date| (date, DateSource::FrontMatter))
+    };
+
+    if config.prefer_date_field {
+        from_date_field().or_else(from_file_name)
     } else {
-        Err(anyhow::anyhow!("Invalid metric format in line {}", line))
+        from_file_name().or_else(from_date_field)
+    }
+}
+
+/// What a journal file looks like on disk, as recorded in `file_meta` once it has been
+/// read successfully.
+#[derive(Debug, Clone, PartialEq)]
+struct FileStamp {
+    /// Modification time in nanoseconds since the Unix epoch.
+    modified_ns: i64,
+    size: i64,
+    content_hash: String,
+}
+
+impl FileStamp {
+    fn modified(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(self.modified_ns.max(0) as u64)
     }
 }
 
-fn should_read_file(path: &str, db: &DbConnection) -> Result<bool> {
+/// A journal file whose content differs from what was last ingested.
+struct ChangedFile {
+    content: Vec<u8>,
+    stamp: FileStamp,
+}
+
+/// How a journal file compares with what was last ingested from it.
+enum OnDisk {
+    /// Missing, or with the modification time and size it was read with.
+    Unchanged,
+    /// The same content under a new modification time, as synced folders leave behind.
+    Touched(FileStamp),
+    Changed(ChangedFile),
+}
+
+/// Reads a file if it changed since it was last ingested. A matching mtime and size
+/// skip the read; otherwise the content hash decides, so a file whose mtime was bumped
+/// without its content changing only has its stamp refreshed.
+fn read_if_changed(path: &str, db: &DbConnection) -> Result<OnDisk> {
     let file_path = Path::new(path);
     if !file_path.exists() {
-        return Ok(false);
+        return Ok(OnDisk::Unchanged);
     }
 
-    let last_modified: SystemTime = metadata(file_path)
-        .and_then(|m| m.modified())
-        .map_err(|e| anyhow::anyhow!("Failed to get metadata for {}: {}", path, e))?;
+    let (_, modified_ns, size) = stat_file(path)?;
 
-    let last_modified_time: DateTime<Utc> = last_modified.into();
-    let last_modified_str = last_modified_time.format(DB_DATE_TIME_FORMAT).to_string();
+    let stored = stored_file_stamp(path, db)?;
+    if stored
+        .as_ref()
+        .is_some_and(|stored| stored.modified_ns == modified_ns && stored.size == size)
+    {
+        return Ok(OnDisk::Unchanged);
+    }
 
-    // Check if file_meta entry exists and matches
-    let meta_matches: i64 = db.lock().unwrap().query_row(
-        "SELECT COUNT(*) FROM file_meta WHERE file_path = ?1 AND last_modified = ?2",
-        params![path, last_modified_str],
-        |row| row.get(0),
-    )?;
+    let content = fs::read(file_path).with_context(|| format!("Failed to read {}", path))?;
+    let stamp = FileStamp {
+        modified_ns,
+        size,
+        content_hash: content_hash(&content),
+    };
+    if stored.is_some_and(|stored| stored.content_hash == stamp.content_hash) {
+        return Ok(OnDisk::Touched(stamp));
+    }
 
-    // Check if file has any metrics stored
-    let metrics_exist: i64 = db.lock().unwrap().query_row(
-        "SELECT COUNT(*) FROM metrics WHERE file_path = ?1",
-        params![path],
-        |row| row.get(0),
-    )?;
+    Ok(OnDisk::Changed(ChangedFile { content, stamp }))
+}
 
-    Ok(meta_matches == 0 || metrics_exist == 0)
-}
-
-pub fn write_metric_to_db(metrics: Metric, db: &DbConnection) -> Result<(), anyhow::Error> {
-    db.lock().unwrap().execute(
-        "INSERT OR REPLACE INTO metrics (file_path, name, value, date,updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![
-            metrics.file_path,
-            metrics.name,
-            metrics.value,
-            metrics.date.format(DB_DATE_FORMAT).to_string(), //inserted 
-            Local::now().format(DB_DATE_TIME_FORMAT).to_string() // updated at
-        ],
-    ).with_context(|| {
-            format!(
-            "Failed to insert metric {:?} into database",
-            metrics
+/// Whether the file at `path` has the modification time and size it had when it was
+/// last read, so it can be skipped without reading its content.
+pub fn is_unchanged_on_disk(path: &str, db: &DbConnection) -> Result<bool> {
+    let (_, modified_ns, size) = stat_file(path)?;
+    Ok(stored_file_stamp(path, db)?
+        .is_some_and(|stored| stored.modified_ns == modified_ns && stored.size == size))
+}
+
+/// The file's modification time, also in nanoseconds since the Unix epoch, and size.
+fn stat_file(path: &str) -> Result<(SystemTime, i64, i64)> {
+    let metadata =
+        fs::metadata(path).with_context(|| format!("Failed to get metadata for {}", path))?;
+    let modified = metadata
+        .modified()
+        .with_context(|| format!("Failed to get last modified time for {}", path))?;
+    Ok((modified, unix_ns(modified), metadata.len() as i64))
+}
+
+/// Nanoseconds since the Unix epoch, as file times are stored.
+pub fn unix_ns(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as i64)
+        .unwrap_or_default()
+}
+
+/// The stamp of the file's last successful ingest. Rows written before content hashes
+/// were recorded, or reset by a rename, have none and count as changed.
+fn stored_file_stamp(path: &str, db: &DbConnection) -> Result<Option<FileStamp>> {
+    let stamp = db
+        .read()?
+        .query_row(
+            "SELECT modified_ns, size, content_hash FROM file_meta WHERE file_path = ?1",
+            [path],
+            |row| {
+                Ok((
+                    row.get::<_, Option<i64>>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            },
         )
-    })?;
-    Ok(())
+        .optional()?;
+
+    Ok(match stamp {
+        Some((Some(modified_ns), Some(size), Some(content_hash))) => Some(FileStamp {
+            modified_ns,
+            size,
+            content_hash,
+        }),
+        _ => None,
+    })
 }
 
-fn update_file_metadata(path: &str, db: &DbConnection) -> Result<()> {
-    let file_path = Path::new(path);
-    let last_modified: SystemTime = file_path
-        .metadata()
-        .and_then(|m| m.modified())
-        .map_err(|e| anyhow::anyhow!("Failed to get metadata for {}: {}", path, e))?;
+fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
 
-    let last_modified_time: DateTime<Utc> = last_modified.into();
-    let last_modified_str = last_modified_time.format(DB_DATE_TIME_FORMAT).to_string();
+fn format_modified(modified: SystemTime) -> String {
+    let modified: DateTime<Utc> = modified.into();
+    modified.format(DB_DATE_TIME_FORMAT).to_string()
+}
+
+/// Stores parsed journal files in one transaction, so a bulk sync commits once per
+/// batch rather than once per file. Either every file in `updates` is stored or none is.
+/// Returns the metrics whose values were replaced, before and after.
+pub fn write_file_updates(db: &DbConnection, updates: &[FileUpdate]) -> Result<MetricChanges> {
+    let mut changes = MetricChanges::default();
+    if updates.is_empty() {
+        return Ok(changes);
+    }
+    let updated_at = Local::now().format(DB_DATE_TIME_FORMAT).to_string();
 
-    db.lock().unwrap().execute(
-        "INSERT OR REPLACE INTO file_meta (file_path, last_modified) VALUES (?1, ?2)",
-        params![path, last_modified_str],
+    let mut conn = db.write()?;
+    // Taken under the write lock, so it orders the batch against anyone else's reads.
+    let stored_ns = unix_ns(SystemTime::now());
+    let tx = conn.transaction()?;
+    for update in updates {
+        match &update.change {
+            FileChange::Restamped(stamp) => {
+                tx.prepare_cached(
+                    "UPDATE file_meta SET last_modified = ?1, modified_ns = ?2, size = ?3, stored_ns = ?4 WHERE file_path = ?5",
+                )?
+                .execute(params![
+                    format_modified(stamp.modified()),
+                    stamp.modified_ns,
+                    stamp.size,
+                    stored_ns,
+                    update.path
+                ])?;
+            }
+            FileChange::Read { entry, diagnostics } => {
+                add_stored_metrics(&tx, &update.path, &mut changes)?;
+                delete_file_rows(&tx, &update.path)?;
+                if let Some(entry) = entry {
+                    insert_file_rows(&tx, &update.path, entry, &updated_at, stored_ns)?;
+                    for metric in &entry.metrics {
+                        changes.add(&metric.name, metric.date);
+                    }
+                }
+                replace_file_diagnostics(&tx, &update.path, diagnostics)?;
+            }
+        }
+    }
+    tx.commit()?;
+
+    Ok(changes)
+}
+
+/// Drops the rows stored for a file before they are replaced with what it contains
+/// now, so values of keys removed from the note, or moved to another date, don't
+/// linger in `metrics` or `metric_events`.
+fn delete_file_rows(conn: &Connection, path: &str) -> Result<()> {
+    for sql in [
+        "DELETE FROM metrics WHERE file_path = ?1",
+        "DELETE FROM metric_events WHERE file_path = ?1",
+        "DELETE FROM file_meta WHERE file_path = ?1",
+    ] {
+        conn.prepare_cached(sql)?.execute([path])?;
+    }
+    Ok(())
+}
+
+/// Stores a file's entry. `file_meta` only records the file once its rows are in, along
+/// with when they were stored.
+fn insert_file_rows(
+    conn: &Connection,
+    path: &str,
+    entry: &ParsedEntry,
+    updated_at: &str,
+    stored_ns: i64,
+) -> Result<()> {
+    let date = entry.period.start.format(DB_DATE_FORMAT).to_string();
+    let period = entry.period.granularity.as_str();
+
+    let mut insert_metric = conn.prepare_cached(
+        "INSERT OR REPLACE INTO metrics (file_path, name, value, date, period, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
     )?;
+    for metric in &entry.metrics {
+        insert_metric
+            .execute(params![
+                path,
+                metric.name,
+                metric.value,
+                metric.date.format(DB_DATE_FORMAT).to_string(),
+                period,
+                updated_at
+            ])
+            .with_context(|| format!("Failed to insert metric {:?} into database", metric))?;
+    }
+    let mut insert_event = conn.prepare_cached(
+        "INSERT INTO metric_events (file_path, name, date, period, time, value, line) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?;
+    for event in &entry.events {
+        insert_event
+            .execute(params![
+                path,
+                event.name,
+                date,
+                period,
+                event
+                    .value
+                    .time
+                    .map(|time| time.format(DB_TIME_FORMAT).to_string()),
+                event.value.value,
+                event.line
+            ])
+            .with_context(|| format!("Failed to insert event {:?} into database", event))?;
+    }
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO file_meta (file_path, last_modified, modified_ns, size, content_hash, entry_date, period, date_source, stored_ns) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?
+    .execute(params![
+        path,
+        format_modified(entry.stamp.modified()),
+        entry.stamp.modified_ns,
+        entry.stamp.size,
+        entry.stamp.content_hash,
+        date,
+        period,
+        entry.date_source.as_str(),
+        stored_ns
+    ])?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        derived::DerivedMetric,
+        inline_fields::FieldPrecedence,
+        metric_value::{Aggregation, MetricType},
+        source_format::{markdown::Markdown, SourceFormat},
+    };
+
+    #[test]
+    fn test_entry_date_falls_back_to_date_field() {
+        let entry = Markdown
+            .parse(
+                "---\ndate: 2025-10-20T07:30:00+02:00\n---\n",
+                &IngestConfig::default(),
+            )
+            .unwrap();
+        let mut config = IngestConfig {
+            date_field: Some("date".to_string()),
+            ..Default::default()
+        };
+        let oct_20 = NaiveDate::from_ymd_opt(2025, 10, 20).unwrap();
+        let oct_21 = NaiveDate::from_ymd_opt(2025, 10, 21).unwrap();
+
+        assert_eq!(
+            entry_date("/vault/Retro.md", entry.as_ref(), &config),
+            Some((oct_20, DateSource::FrontMatter))
+        );
+        assert_eq!(
+            entry_date("/vault/2025-10-21.md", entry.as_ref(), &config),
+            Some((oct_21, DateSource::FileName))
+        );
+
+        config.prefer_date_field = true;
+        assert_eq!(
+            entry_date("/vault/2025-10-21.md", entry.as_ref(), &config),
+            Some((oct_20, DateSource::FrontMatter))
+        );
+
+        config.date_field = None;
+        assert_eq!(entry_date("/vault/Retro.md", entry.as_ref(), &config), None);
+    }
+
+    /// Looks `metric` up in `note` with inline fields turned on.
+    fn find(note: &str, metric: MetricDefinition, config: IngestConfig) -> Option<FoundValue> {
+        let config = IngestConfig {
+            tracked_metrics: vec![metric],
+            inline_fields: true,
+            ..config
+        };
+        let entry = Markdown.parse(note, &config).unwrap();
+        metric_values(&config.tracked_metrics[0], entry.as_ref(), &config)
+            .into_iter()
+            .next()
+    }
+
+    #[test]
+    fn test_field_precedence_between_front_matter_and_inline_fields() {
+        let note = "---\npages_read: 12\n---\npages_read:: 20\nmood:: 7\n";
+        let value = |name: &str, field_precedence| {
+            let config = IngestConfig {
+                field_precedence,
+                ..Default::default()
+            };
+            find(
+                note,
+                MetricDefinition::new(name, MetricType::Integer),
+                config,
+            )
+            .map(|found| found.value.unwrap().as_f64())
+        };
+
+        assert_eq!(
+            value("pages_read", FieldPrecedence::FrontMatter),
+            Some(12.0)
+        );
+        assert_eq!(value("pages_read", FieldPrecedence::Inline), Some(20.0));
+        assert_eq!(value("mood", FieldPrecedence::FrontMatter), Some(7.0));
+        assert_eq!(value("workout", FieldPrecedence::Inline), None);
+    }
+
+    #[test]
+    fn test_checkboxes_record_boolean_completions() {
+        let note = "---\nstretch: true\n---\n- [x] Meditate\n- [ ] stretch\n- [ ] walk\n";
+        let value = |name: &str, label: &str| {
+            let metric = MetricDefinition {
+                checkbox_label: Some(label.parse().unwrap()),
+                ..MetricDefinition::new(name, MetricType::Boolean)
+            };
+            find(note, metric, IngestConfig::default()).map(|found| found.value.unwrap())
+        };
+
+        assert_eq!(
+            value("meditation", "meditate"),
+            Some(MetricValue::Boolean(true))
+        );
+        assert_eq!(value("walk", "walk"), Some(MetricValue::Boolean(false)));
+        // An explicit value wins over the checklist.
+        assert_eq!(
+            value("stretch", "stretch"),
+            Some(MetricValue::Boolean(true))
+        );
+        assert_eq!(value("read", "read"), None);
+    }
+
+    #[test]
+    fn test_aliases_and_normalized_keys() {
+        let note = "---\nPages-Read: 12\nHabits:\n  Work-Out: 30\n---\nMood:: 7\n";
+        let value = |name: &str, aliases: &[&str], normalize_keys| {
+            let metric = MetricDefinition {
+                aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+                ..MetricDefinition::new(name, MetricType::Integer)
+            };
+            let config = IngestConfig {
+                normalize_keys,
+                ..Default::default()
+            };
+            find(note, metric, config).map(|found| found.value.unwrap().as_f64())
+        };
+
+        assert_eq!(value("pages_read", &[], false), None);
+        assert_eq!(value("pages_read", &[], true), Some(12.0));
+        assert_eq!(
+            value("reading", &["pages", "Pages-Read"], false),
+            Some(12.0)
+        );
+        assert_eq!(value("habits.work_out", &[], true), Some(30.0));
+        assert_eq!(value("mood", &[], true), Some(7.0));
+    }
+
+    #[test]
+    fn test_invalid_values_point_at_their_line() {
+        let note = "---\ntitle: Tuesday\nhabits:\n  reading: lots\n---\n# Notes\n\nmood:: great\n";
+
+        let reading = find(
+            note,
+            MetricDefinition::new("habits.reading", MetricType::Integer),
+            IngestConfig::default(),
+        )
+        .unwrap();
+        assert!(reading.value.is_err());
+        assert_eq!(reading.raw, "lots");
+        assert_eq!(reading.line, Some(4));
+
+        let mood = find(
+            note,
+            MetricDefinition::new("mood", MetricType::Integer),
+            IngestConfig::default(),
+        )
+        .unwrap();
+        assert!(mood.value.is_err());
+        assert_eq!(mood.raw, "great");
+        assert_eq!(mood.line, Some(8));
+
+        let broken = Markdown.parse(
+            "---\ntitle: Tuesday\npages: [1, 2\n---\n",
+            &IngestConfig::default(),
+        );
+        let line = broken.err().and_then(|e| e.line);
+        assert!(matches!(line, Some(3..=4)));
+    }
+
+    fn test_db() -> DbConnection {
+        DbConnection::open_in_memory().unwrap()
+    }
+
+    fn stored_pages(db: &DbConnection) -> Option<f64> {
+        db.read()
+            .unwrap()
+            .query_row(
+                "SELECT value FROM metrics WHERE name = 'pages_read'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_changes_are_detected_by_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("2025-10-21.md");
+        let path_str = path.to_str().unwrap();
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            ..Default::default()
+        };
+        let db = test_db();
+        let write = |content: &str, modified: SystemTime| {
+            fs::write(&path, content).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let noon = UNIX_EPOCH + Duration::from_secs(1_761_048_000);
+
+        // A note that fails to parse isn't recorded, so it's read again once fixed.
+        write("---\npages_read: [\n---\n", noon);
+        read_journal_file(path_str, &config, &db).unwrap();
+        assert!(stored_file_stamp(path_str, &db).unwrap().is_none());
+
+        write("---\npages_read: 12\n---\n", noon);
+        read_journal_file(path_str, &config, &db).unwrap();
+        assert_eq!(stored_pages(&db), Some(12.0));
+        assert!(matches!(
+            read_if_changed(path_str, &db).unwrap(),
+            OnDisk::Unchanged
+        ));
+
+        // A second save within the same second is still picked up.
+        write(
+            "---\npages_read: 13\n---\n",
+            noon + Duration::from_millis(400),
+        );
+        read_journal_file(path_str, &config, &db).unwrap();
+        assert_eq!(stored_pages(&db), Some(13.0));
+
+        // A touched file with the same content only has its stamp refreshed.
+        let touched = noon + Duration::from_secs(60);
+        write("---\npages_read: 13\n---\n", touched);
+        assert!(matches!(
+            read_if_changed(path_str, &db).unwrap(),
+            OnDisk::Touched(_)
+        ));
+        read_journal_file(path_str, &config, &db).unwrap();
+        assert_eq!(stored_pages(&db), Some(13.0));
+        let stamp = stored_file_stamp(path_str, &db).unwrap().unwrap();
+        assert_eq!(
+            stamp.modified_ns,
+            touched.duration_since(UNIX_EPOCH).unwrap().as_nanos() as i64
+        );
+    }
+
+    #[test]
+    fn test_reingest_replaces_the_files_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Retro.md");
+        let path_str = path.to_str().unwrap();
+        let config = IngestConfig {
+            tracked_metrics: vec![
+                MetricDefinition::new("pages_read", MetricType::Integer),
+                MetricDefinition::new("mood", MetricType::Integer),
+            ],
+            date_field: Some("date".to_string()),
+            ..Default::default()
+        };
+        let db = test_db();
+        let stored = || -> Vec<(String, String)> {
+            let conn = db.read().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT name, date FROM metrics ORDER BY name")
+                .unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+
+        fs::write(
+            &path,
+            "---\ndate: 2025-10-20\npages_read: 12\nmood: 7\n---\n",
+        )
+        .unwrap();
+        read_journal_file(path_str, &config, &db).unwrap();
+        assert_eq!(stored().len(), 2);
+
+        fs::write(&path, "---\ndate: 2025-10-21\nmood: 6\n---\n").unwrap();
+        read_journal_file(path_str, &config, &db).unwrap();
+        assert_eq!(
+            stored(),
+            vec![("mood".to_string(), "2025-10-21".to_string())]
+        );
+
+        // Both the values replaced and the ones replacing them count as changed.
+        fs::write(&path, "---\ndate: 2025-10-23\npages_read: 3\n---\n").unwrap();
+        let update = parse_journal_file(path_str, &config, &db).unwrap();
+        let changes = write_file_updates(&db, update.as_slice()).unwrap();
+        assert_eq!(
+            changes.names().collect::<Vec<_>>(),
+            vec!["mood", "pages_read"]
+        );
+        assert_eq!(
+            changes.dates(),
+            Some((
+                NaiveDate::from_ymd_opt(2025, 10, 21).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 10, 23).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_note_that_stops_parsing_loses_its_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("2025-10-20.md");
+        let path_str = path.to_str().unwrap();
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            ..Default::default()
+        };
+        let db = test_db();
+
+        fs::write(&path, "---\npages_read: 12\n---\n").unwrap();
+        read_journal_file(path_str, &config, &db).unwrap();
+        assert_eq!(stored_pages(&db), Some(12.0));
+
+        fs::write(&path, "---\npages_read: [12\n---\n").unwrap();
+        read_journal_file(path_str, &config, &db).unwrap();
+        assert_eq!(stored_pages(&db), None);
+        assert!(stored_file_stamp(path_str, &db).unwrap().is_none());
+        let conn = db.read().unwrap();
+        let (events, problems): (i64, i64) = conn
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM metric_events), (SELECT COUNT(*) FROM ingest_diagnostics)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((events, problems), (0, 1));
+    }
+
+    #[test]
+    fn test_parsed_files_are_written_in_one_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            ..Default::default()
+        };
+        let db = test_db();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("2025-10-20.md"), "---\npages_read: 12\n---\n").unwrap();
+        fs::write(path("2025-10-21.md"), "---\npages_read: 8\n---\n").unwrap();
+        fs::write(path("2025-10-22.md"), "---\npages_read: [\n---\n").unwrap();
+
+        let updates: Vec<FileUpdate> = ["2025-10-20.md", "2025-10-21.md", "2025-10-22.md"]
+            .map(path)
+            .iter()
+            .filter_map(|path| parse_journal_file(path, &config, &db).unwrap())
+            .collect();
+        assert_eq!(updates.len(), 3);
+        // Parsing alone stores nothing.
+        assert_eq!(stored_pages(&db), None);
+
+        write_file_updates(&db, &updates).unwrap();
+        let conn = db.read().unwrap();
+        let total: f64 = conn
+            .query_row("SELECT SUM(value) FROM metrics", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(total, 20.0);
+        let problems: Vec<String> = conn
+            .prepare("SELECT file_path FROM ingest_diagnostics")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(problems, vec![path("2025-10-22.md")]);
+    }
+
+    #[test]
+    fn test_weekly_notes_are_stored_as_weeks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("2025-W42.md");
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            // A weekly note's name decides its period even when it sets a date.
+            date_field: Some("date".to_string()),
+            ..Default::default()
+        };
+        let db = test_db();
+
+        fs::write(&path, "---\ndate: 2025-10-15\npages_read: 90\n---\n").unwrap();
+        read_journal_file(path.to_str().unwrap(), &config, &db).unwrap();
+
+        let conn = db.read().unwrap();
+        let stored: (String, String, f64) = conn
+            .query_row("SELECT date, period, value FROM metrics", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(stored, ("2025-10-13".to_string(), "week".to_string(), 90.0));
+    }
+
+    #[test]
+    fn test_repeated_entries_are_stored_as_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("2025-10-20.md");
+        let config = IngestConfig {
+            tracked_metrics: vec![
+                MetricDefinition::new("run_km", MetricType::Float),
+                MetricDefinition::new("pages_read", MetricType::Integer),
+            ],
+            inline_fields: true,
+            ..Default::default()
+        };
+        let db = test_db();
+        let value = |name: &str| -> f64 {
+            let conn = db.read().unwrap();
+            conn.query_row("SELECT value FROM metrics WHERE name = ?1", [name], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+
+        fs::write(
+            &path,
+            "---\npages_read: [10, 25]\n---\n- 18:00 run_km:: 3\n- 07:30 run_km:: 5\n",
+        )
+        .unwrap();
+        read_journal_file(path.to_str().unwrap(), &config, &db).unwrap();
+
+        assert_eq!(value("run_km"), 8.0);
+        assert_eq!(value("pages_read"), 35.0);
+        let runs = crate::db::metrics::get_logged_values(
+            &db,
+            "run_km",
+            NaiveDate::from_ymd_opt(2025, 10, 20).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            runs.iter()
+                .map(|run| (run.time.as_deref(), run.value, run.line))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some("18:00:00"), 3.0, Some(4)),
+                (Some("07:30:00"), 5.0, Some(5))
+            ]
+        );
+
+        crate::db::metrics::reaggregate_metric(&db, "run_km", Aggregation::Last).unwrap();
+        assert_eq!(value("run_km"), 3.0);
+
+        crate::db::metrics::reaggregate_metric(&db, "pages_read", Aggregation::Max).unwrap();
+        assert_eq!(value("pages_read"), 25.0);
+    }
+
+    #[test]
+    fn test_derived_metrics_follow_their_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("2025-10-20.md");
+        let derived = |formula: &str| {
+            vec![DerivedMetric::new("reading_minutes", formula, MetricType::Integer).unwrap()]
+        };
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            derived_metrics: derived("pages_read * 2"),
+            ..Default::default()
+        };
+        let db = test_db();
+        let minutes = || -> f64 {
+            let conn = db.read().unwrap();
+            conn.query_row(
+                "SELECT value FROM metrics WHERE name = 'reading_minutes'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        fs::write(&path, "---\npages_read: 12\n---\n").unwrap();
+        read_journal_file(path.to_str().unwrap(), &config, &db).unwrap();
+        assert_eq!(minutes(), 24.0);
+
+        let changes =
+            crate::db::metrics::recompute_derived_metrics(&db, &derived("pages_read * 3")).unwrap();
+        assert_eq!(minutes(), 36.0);
+        assert_eq!(changes.names().collect::<Vec<_>>(), vec!["reading_minutes"]);
+
+        // 12 / 5 isn't a whole number, so the entry gets a diagnostic instead.
+        let failures = || -> i64 {
+            let conn = db.read().unwrap();
+            conn.query_row(
+                "SELECT COUNT(*) FROM ingest_diagnostics WHERE metric = 'reading_minutes'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        crate::db::metrics::recompute_derived_metrics(&db, &derived("pages_read / 5")).unwrap();
+        assert_eq!(failures(), 1);
+        crate::db::metrics::recompute_derived_metrics(&db, &derived("pages_read * 2")).unwrap();
+        assert_eq!((minutes(), failures()), (24.0, 0));
+    }
+
+    #[test]
+    fn test_suffixed_values_are_converted_to_the_metric_unit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("2025-10-20.md");
+        fs::write(&path, "---\nrun: [5km, 3mi]\nswim: 2 laps\n---\n").unwrap();
+        let mut run = MetricDefinition::new("run", MetricType::Float);
+        run.unit = Some("km".parse().unwrap());
+        let mut swim = MetricDefinition::new("swim", MetricType::Integer);
+        swim.unit = Some("m".parse().unwrap());
+        let config = IngestConfig {
+            tracked_metrics: vec![run, swim],
+            ..Default::default()
+        };
+        let db = test_db();
+
+        read_journal_file(path.to_str().unwrap(), &config, &db).unwrap();
+
+        let conn = db.read().unwrap();
+        let run: f64 = conn
+            .query_row("SELECT value FROM metrics WHERE name = 'run'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!((run * 100.0).round() / 100.0, 9.83);
+        let swim_reason: String = conn
+            .query_row(
+                "SELECT reason FROM ingest_diagnostics WHERE metric = 'swim'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(swim_reason.contains("can't be converted to m"));
+    }
+}
@@ -0,0 +1,338 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    core::{
+        change_events::{emit_files_removed, RemovedFiles},
+        load_journal_filter,
+        period::Period,
+        read_dailies::read_dailies_dir,
+        read_journal::is_unchanged_on_disk,
+        root_filename_pattern,
+        sync_queue::SyncQueue,
+    },
+    db::{
+        journal_roots::get_enabled_journal_roots,
+        utils::{
+            delete_file_rows_stored_before, delete_file_rows_where, get_date_field,
+            get_reconcile_interval, stored_file_paths,
+        },
+    },
+    DbConnection,
+};
+
+/// How often a turned-off periodic check looks whether it has been turned back on.
+const DISABLED_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What comparing the journal on disk with the database found.
+#[derive(Debug, Default, PartialEq)]
+pub struct Reconciliation {
+    /// Files that are new or look changed since they were last read.
+    pub changed: Vec<String>,
+    /// Stored files that weren't found by the scan, because they are gone from disk or
+    /// no longer journal files. Their rows have been dropped.
+    pub removed: RemovedFiles,
+    pub unchanged: usize,
+}
+
+/// Compares the journal files found under `roots` by a scan that began at
+/// `scan_started` with what is stored. Files stored under those roots that weren't
+/// found are dropped, unless a sync stored them after the scan began; new and changed
+/// files are returned for reading. Files under other roots, such as ones that couldn't
+/// be scanned, are left alone.
+pub fn reconcile(
+    db: &DbConnection,
+    roots: &[String],
+    file_paths: Vec<String>,
+    scan_started: SystemTime,
+) -> Result<Reconciliation, anyhow::Error> {
+    let found: HashSet<&str> = file_paths.iter().map(String::as_str).collect();
+    let stored = {
+        let conn = db.read()?;
+        stored_file_paths(&conn)?
+    };
+
+    let removed: Vec<String> = stored
+        .into_iter()
+        .filter(|path| {
+            !found.contains(path.as_str())
+                && roots.iter().any(|root| Path::new(path).starts_with(root))
+        })
+        .collect();
+    // The watcher and sync workers run meanwhile, so a file created or renamed into
+    // place after the scan began may have been stored since.
+    let removed = if removed.is_empty() {
+        RemovedFiles::default()
+    } else {
+        delete_file_rows_stored_before(db, removed, scan_started)?
+    };
+
+    let mut reconciliation = Reconciliation {
+        removed,
+        ..Default::default()
+    };
+    for path in file_paths {
+        // A file that can't be checked is read, which reports what is wrong with it.
+        if is_unchanged_on_disk(&path, db).unwrap_or(false) {
+            reconciliation.unchanged += 1;
+        } else {
+            reconciliation.changed.push(path);
+        }
+    }
+    Ok(reconciliation)
+}
+
+/// Drops the rows of stored files that the current settings no longer make journal
+/// entries: those their root's globs or formats now leave out, and those whose name
+/// gives no date when there is no date field to take one from. Called when those
+/// settings change, before the resync that reads what is left.
+pub fn purge_non_journal_files(db: &DbConnection) -> Result<RemovedFiles, anyhow::Error> {
+    let date_field = get_date_field(db)?;
+    let roots = get_enabled_journal_roots(db)?
+        .into_iter()
+        .map(|root| {
+            let filter = load_journal_filter(db, &root.path)?;
+            let pattern = root_filename_pattern(db, &root)?;
+            Ok((root, filter, pattern))
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    delete_file_rows_where(db, |path| {
+        let Some((_, filter, pattern)) = roots
+            .iter()
+            .filter(|(root, _, _)| root.contains(path))
+            .max_by_key(|(root, _, _)| root.path.len())
+        else {
+            return false;
+        };
+        let names_a_date = || {
+            path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(Period::from_stem)
+                .is_some()
+                || pattern.date_from_path(path).is_some()
+        };
+        !filter.matches(path) || (date_field.is_none() && !names_a_date())
+    })
+}
+
+/// Scans every enabled journal root and reconciles it with the database, telling the
+/// frontend about the files that were dropped.
+pub fn reconcile_journal(app_handle: &AppHandle) -> Result<Reconciliation, anyhow::Error> {
+    let db = app_handle.state::<DbConnection>();
+    let scan_started = SystemTime::now();
+    let mut scanned = Vec::new();
+    let mut file_paths = Vec::new();
+    for root in get_enabled_journal_roots(&db)? {
+        match read_dailies_dir(&root, app_handle.clone()) {
+            Ok(paths) => {
+                file_paths.extend(paths);
+                scanned.push(root.path);
+            }
+            Err(e) => eprintln!("Failed to scan journal root {}: {}", root.path, e),
+        }
+    }
+    let reconciliation = reconcile(&db, &scanned, file_paths, scan_started)?;
+    emit_files_removed(app_handle, &reconciliation.removed);
+    Ok(reconciliation)
+}
+
+/// Reconciles the journal every so often, as configured, to catch changes the watcher
+/// missed. Changed files join whatever the sync workers are already doing.
+pub fn start_reconcile_schedule(app_handle: AppHandle) {
+    thread::spawn(move || loop {
+        let interval = {
+            let db = app_handle.state::<DbConnection>();
+            get_reconcile_interval(&db).unwrap_or_else(|e| {
+                eprintln!("Failed to load reconcile interval: {}", e);
+                None
+            })
+        };
+        let Some(interval) = interval else {
+            thread::sleep(DISABLED_POLL_INTERVAL);
+            continue;
+        };
+        thread::sleep(interval);
+
+        match reconcile_journal(&app_handle) {
+            Ok(reconciliation) => {
+                let removed = reconciliation.removed.paths.len();
+                if removed > 0 || !reconciliation.changed.is_empty() {
+                    println!(
+                        "Reconciled journal: {} changed, {} removed",
+                        reconciliation.changed.len(),
+                        removed
+                    );
+                }
+                app_handle
+                    .state::<SyncQueue>()
+                    .enqueue(reconciliation.changed);
+            }
+            Err(e) => eprintln!("Failed to reconcile journal: {}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        metric_value::{MetricDefinition, MetricType},
+        read_journal::read_journal_file,
+        IngestConfig,
+    };
+    use crate::db::{
+        journal_roots::{upsert_journal_root, JournalRoot},
+        utils::{set_list_setting, EXCLUDE_GLOBS_SETTING},
+    };
+    use std::fs;
+
+    #[test]
+    fn test_reconcile_finds_offline_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let db = DbConnection::open_in_memory().unwrap();
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            ..Default::default()
+        };
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+
+        for name in ["2025-10-20.md", "2025-10-21.md", "2025-10-22.md"] {
+            fs::write(path(name), "---\npages_read: 12\n---\n").unwrap();
+            read_journal_file(&path(name), &config, &db).unwrap();
+        }
+        // While the app was closed: one note deleted, one edited and one added.
+        fs::remove_file(path("2025-10-20.md")).unwrap();
+        fs::write(path("2025-10-21.md"), "---\npages_read: 300\n---\n").unwrap();
+        fs::write(path("2025-10-23.md"), "---\npages_read: 5\n---\n").unwrap();
+
+        let found = ["2025-10-21.md", "2025-10-22.md", "2025-10-23.md"]
+            .map(path)
+            .to_vec();
+        let reconciliation = reconcile(&db, &[root], found, SystemTime::now()).unwrap();
+
+        assert_eq!(
+            reconciliation.changed,
+            vec![path("2025-10-21.md"), path("2025-10-23.md")]
+        );
+        assert_eq!(reconciliation.removed.paths, vec![path("2025-10-20.md")]);
+        assert_eq!(
+            reconciliation.removed.changes.names().collect::<Vec<_>>(),
+            vec!["pages_read"]
+        );
+        assert_eq!(reconciliation.unchanged, 1);
+        let stored: i64 = db
+            .read()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM metrics WHERE file_path = ?1",
+                [path("2025-10-20.md")],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stored, 0);
+    }
+
+    #[test]
+    fn test_reconcile_keeps_files_stored_after_the_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let db = DbConnection::open_in_memory().unwrap();
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            ..Default::default()
+        };
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("2025-10-20.md"), "---\npages_read: 12\n---\n").unwrap();
+        read_journal_file(&path("2025-10-20.md"), &config, &db).unwrap();
+        let scan_started = SystemTime::now();
+        let found = vec![path("2025-10-20.md")];
+
+        // Saved and read by the sync workers after the scan finished.
+        fs::write(path("2025-10-21.md"), "---\npages_read: 8\n---\n").unwrap();
+        read_journal_file(&path("2025-10-21.md"), &config, &db).unwrap();
+
+        let reconciliation = reconcile(&db, &[root], found, scan_started).unwrap();
+        assert!(reconciliation.removed.paths.is_empty());
+        let stored: i64 = db
+            .read()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM metrics", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, 2);
+    }
+
+    #[test]
+    fn test_reconcile_drops_files_that_are_no_longer_journal_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let db = DbConnection::open_in_memory().unwrap();
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            ..Default::default()
+        };
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::create_dir(dir.path().join("templates")).unwrap();
+        for name in ["2025-10-20.md", "templates/2025-10-21.md"] {
+            fs::write(path(name), "---\npages_read: 12\n---\n").unwrap();
+            read_journal_file(&path(name), &config, &db).unwrap();
+        }
+
+        // The template is still on disk, but an exclude glob now keeps it out of scans.
+        let reconciliation =
+            reconcile(&db, &[root], vec![path("2025-10-20.md")], SystemTime::now()).unwrap();
+
+        assert_eq!(
+            reconciliation.removed.paths,
+            vec![path("templates/2025-10-21.md")]
+        );
+        assert_eq!(reconciliation.unchanged, 1);
+    }
+
+    #[test]
+    fn test_excluding_a_folder_drops_its_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+        let db = DbConnection::open_in_memory().unwrap();
+        upsert_journal_root(
+            &db,
+            &JournalRoot {
+                path: root,
+                filename_pattern: None,
+                enabled: true,
+            },
+        )
+        .unwrap();
+        let config = IngestConfig {
+            tracked_metrics: vec![MetricDefinition::new("pages_read", MetricType::Integer)],
+            ..Default::default()
+        };
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::create_dir(dir.path().join("templates")).unwrap();
+        for name in ["2025-10-20.md", "templates/2025-10-21.md"] {
+            fs::write(path(name), "---\npages_read: 12\n---\n").unwrap();
+            read_journal_file(&path(name), &config, &db).unwrap();
+        }
+
+        set_list_setting(&db, EXCLUDE_GLOBS_SETTING, &["templates".to_string()]).unwrap();
+        let removed = purge_non_journal_files(&db).unwrap();
+
+        assert_eq!(removed.paths, vec![path("templates/2025-10-21.md")]);
+        let stored: Vec<String> = {
+            let conn = db.read().unwrap();
+            let mut stmt = conn.prepare("SELECT file_path FROM metrics").unwrap();
+            stmt.query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap()
+        };
+        assert_eq!(stored, vec![path("2025-10-20.md")]);
+    }
+}
@@ -0,0 +1,104 @@
+use chrono::NaiveDate;
+use serde_yaml::Value;
+
+use crate::core::{
+    metric_value::MetricDefinition,
+    source_format::{
+        properties::{lookup_key, lookup_metric, value_date},
+        FormatError, FoundValue, SourceEntry, SourceFormat,
+    },
+    IngestConfig,
+};
+
+/// Daily logs exported as one JSON object per file, such as
+/// `{"date": "2025-10-20", "pages_read": 12, "habits": {"reading": 20}}`.
+pub struct Json;
+
+impl SourceFormat for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json"]
+    }
+
+    fn parse<'a>(
+        &self,
+        content: &'a str,
+        _config: &IngestConfig,
+    ) -> Result<Box<dyn SourceEntry + 'a>, FormatError> {
+        let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+        // Parsed straight into a YAML value so nested keys resolve like front matter.
+        let value: Value = serde_json::from_str(content).map_err(|e| FormatError {
+            message: format!("Failed to parse the JSON: {}", e),
+            line: Some(e.line()),
+        })?;
+        if !value.is_mapping() {
+            return Err(FormatError {
+                message: "Expected a JSON object".to_string(),
+                line: None,
+            });
+        }
+
+        Ok(Box::new(JsonEntry { content, value }))
+    }
+}
+
+struct JsonEntry<'a> {
+    content: &'a str,
+    value: Value,
+}
+
+impl SourceEntry for JsonEntry<'_> {
+    fn date_field(&self, field: &str) -> Option<NaiveDate> {
+        lookup_metric(&self.value, field).and_then(value_date)
+    }
+
+    fn field_values(&self, metric: &MetricDefinition, config: &IngestConfig) -> Vec<FoundValue> {
+        metric
+            .keys()
+            .find_map(|key| {
+                lookup_key(&self.value, key, config.normalize_keys).map(|raw| (key, raw))
+            })
+            .map(|(key, raw)| FoundValue::from_value(metric, raw, key_line(self.content, key)))
+            .unwrap_or_default()
+    }
+}
+
+/// The 1-based line holding the last segment of `key` as a quoted key, which is a best
+/// guess for dotted paths and minified files.
+fn key_line(content: &str, key: &str) -> Option<usize> {
+    let segment = format!("\"{}\"", key.rsplit('.').next().unwrap_or(key));
+    content
+        .lines()
+        .position(|line| line.contains(&segment))
+        .map(|index| index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::metric_value::{MetricType, MetricValue};
+
+    #[test]
+    fn test_json_entries() {
+        let content =
+            "{\n  \"date\": \"2025-10-20\",\n  \"habits\": {\n    \"reading\": 20\n  }\n}\n";
+        let metric = MetricDefinition::new("habits.reading", MetricType::Integer);
+        let config = IngestConfig::default();
+        let entry = Json.parse(content, &config).unwrap();
+        let found = entry.field_values(&metric, &config).remove(0);
+
+        assert_eq!(
+            entry.date_field("date"),
+            NaiveDate::from_ymd_opt(2025, 10, 20)
+        );
+        assert_eq!(found.value.unwrap(), MetricValue::Integer(20));
+        assert_eq!(found.line, Some(4));
+
+        let broken = Json.parse("{\n  \"date\": \n", &config).err().unwrap();
+        assert_eq!(broken.line, Some(3));
+        assert!(Json.parse("[1, 2]", &config).is_err());
+    }
+}
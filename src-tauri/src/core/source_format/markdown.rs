@@ -0,0 +1,463 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::de::IgnoredAny;
+use serde_yaml::Value;
+
+use crate::core::{
+    checkboxes::{parse_checkboxes, Checkbox},
+    inline_fields::{parse_inline_fields, FieldPrecedence, InlineField, InlineFields},
+    metric_value::{normalize_metric_key, MetricDefinition},
+    source_format::{
+        properties::{lookup_key, lookup_metric, value_date},
+        FormatError, FoundValue, SourceEntry, SourceFormat,
+    },
+    IngestConfig,
+};
+
+/// Markdown notes with optional front matter, as written by Obsidian, Foam, Logseq and
+/// most static site tools: `---` YAML, `+++` TOML, or `;;;` or bare `{}` JSON.
+pub struct Markdown;
+
+impl SourceFormat for Markdown {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["md"]
+    }
+
+    fn parse<'a>(
+        &self,
+        content: &'a str,
+        config: &IngestConfig,
+    ) -> Result<Box<dyn SourceEntry + 'a>, FormatError> {
+        Ok(Box::new(Note::parse(content, config)?))
+    }
+}
+
+/// The places a note can set metric values in.
+struct Note<'a> {
+    front_matter_block: Option<FrontMatterBlock<'a>>,
+    front_matter: Option<Value>,
+    inline_fields: InlineFields,
+    checkboxes: Vec<Checkbox>,
+    /// 1-based line of the file the body starts on.
+    body_line: usize,
+}
+
+impl<'a> Note<'a> {
+    /// Parses the front matter, and the body as far as `config` asks for.
+    fn parse(content: &'a str, config: &IngestConfig) -> Result<Self, FormatError> {
+        let (front_matter_block, body) = split_front_matter(content);
+        let front_matter = match &front_matter_block {
+            Some(block) => block.parse()?,
+            None => None,
+        };
+
+        let inline_fields = if config.inline_fields {
+            parse_inline_fields(body)
+        } else {
+            InlineFields::new()
+        };
+        let checkboxes = if config
+            .tracked_metrics
+            .iter()
+            .any(|metric| metric.checkbox_label.is_some())
+        {
+            parse_checkboxes(body)
+        } else {
+            Vec::new()
+        };
+
+        // The body is a suffix of `content`, so everything before it is front matter.
+        let body_line = content[..content.len() - body.len()].matches('\n').count() + 1;
+
+        Ok(Note {
+            front_matter_block,
+            front_matter,
+            inline_fields,
+            checkboxes,
+            body_line,
+        })
+    }
+}
+
+impl SourceEntry for Note<'_> {
+    fn date_field(&self, field: &str) -> Option<NaiveDate> {
+        lookup_metric(self.front_matter.as_ref()?, field).and_then(value_date)
+    }
+
+    /// Takes the metric's values from front matter or inline fields, letting the
+    /// configured precedence decide when both set it.
+    fn field_values(&self, metric: &MetricDefinition, config: &IngestConfig) -> Vec<FoundValue> {
+        let normalize = config.normalize_keys;
+        let from_front_matter = || {
+            let Some(front_matter) = self.front_matter.as_ref() else {
+                return Vec::new();
+            };
+            let Some((key, raw)) = metric
+                .keys()
+                .find_map(|key| lookup_key(front_matter, key, normalize).map(|raw| (key, raw)))
+            else {
+                return Vec::new();
+            };
+            let line = self
+                .front_matter_block
+                .as_ref()
+                .and_then(|block| block.line_of(key, normalize));
+            FoundValue::from_value(metric, raw, line)
+        };
+        let from_inline = || {
+            let Some(fields) = metric
+                .keys()
+                .find_map(|key| inline_fields(&self.inline_fields, key, normalize))
+            else {
+                return Vec::new();
+            };
+            fields
+                .iter()
+                .map(|field| {
+                    let found = FoundValue::from_log_text(
+                        metric,
+                        &field.value,
+                        Some(self.body_line + field.line - 1),
+                    );
+                    FoundValue {
+                        time: found.time.or(field.time),
+                        ..found
+                    }
+                })
+                .collect()
+        };
+
+        match config.field_precedence {
+            FieldPrecedence::FrontMatter => or_else(from_front_matter(), from_inline),
+            FieldPrecedence::Inline => or_else(from_inline(), from_front_matter),
+        }
+    }
+
+    fn checkboxes(&self) -> &[Checkbox] {
+        &self.checkboxes
+    }
+}
+
+/// The values found in the preferred source, or else in the other one.
+fn or_else(found: Vec<FoundValue>, fallback: impl FnOnce() -> Vec<FoundValue>) -> Vec<FoundValue> {
+    if found.is_empty() {
+        fallback()
+    } else {
+        found
+    }
+}
+
+fn inline_fields<'a>(
+    fields: &'a InlineFields,
+    key: &str,
+    normalize: bool,
+) -> Option<&'a [InlineField]> {
+    if let Some(found) = fields.get(key) {
+        return Some(found);
+    }
+    if !normalize {
+        return None;
+    }
+
+    let key = normalize_metric_key(key);
+    fields
+        .iter()
+        .find(|(name, _)| normalize_metric_key(name) == key)
+        .map(|(_, found)| found.as_slice())
+}
+
+/// The syntax of a note's front matter, told apart by its opening delimiter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrontMatterSyntax {
+    /// Fenced by `---` lines.
+    Yaml,
+    /// Fenced by `+++` lines, as in Hugo and Zola.
+    Toml,
+    /// Fenced by `;;;` lines, or a bare `{ ... }` object at the top of the file.
+    Json,
+}
+
+impl fmt::Display for FrontMatterSyntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FrontMatterSyntax::Yaml => "YAML",
+            FrontMatterSyntax::Toml => "TOML",
+            FrontMatterSyntax::Json => "JSON",
+        })
+    }
+}
+
+/// The raw front matter of a note.
+#[derive(Debug, Clone, PartialEq)]
+struct FrontMatterBlock<'a> {
+    syntax: FrontMatterSyntax,
+    text: &'a str,
+    /// 1-based line of the file the text starts on.
+    first_line: usize,
+}
+
+impl FrontMatterBlock<'_> {
+    /// Parses the block with the parser for its syntax into the same value tree, so
+    /// metrics are looked up and typed the same way whatever the syntax.
+    fn parse(&self) -> Result<Option<Value>, FormatError> {
+        if self.text.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let value = match self.syntax {
+            FrontMatterSyntax::Yaml => serde_yaml::from_str(self.text)
+                .map_err(|e| self.error(&e, e.location().map(|location| location.line())))?,
+            FrontMatterSyntax::Toml => toml::from_str::<toml::Table>(self.text)
+                .map(|table| toml_to_yaml(toml::Value::Table(table)))
+                .map_err(|e| {
+                    let line = e
+                        .span()
+                        .map(|span| self.text[..span.start].matches('\n').count() + 1);
+                    self.error(&e, line)
+                })?,
+            FrontMatterSyntax::Json => {
+                serde_json::from_str(self.text).map_err(|e| self.error(&e, Some(e.line())))?
+            }
+        };
+        Ok(Some(value))
+    }
+
+    /// A parse error at `line` of the block, reported against the line of the file.
+    fn error(&self, e: &dyn fmt::Display, line: Option<usize>) -> FormatError {
+        FormatError {
+            message: format!("Failed to parse the {} front matter: {}", self.syntax, e),
+            line: line.map(|line| self.first_line + line - 1),
+        }
+    }
+
+    /// The 1-based line of the file that sets `key`. For a dotted path this is the line
+    /// of its last segment, which is a best guess when the same key appears under
+    /// several parents.
+    fn line_of(&self, key: &str, normalize: bool) -> Option<usize> {
+        let normalized = |key: &str| {
+            if normalize {
+                normalize_metric_key(key)
+            } else {
+                key.to_string()
+            }
+        };
+        let key = normalized(key);
+        let separator = match self.syntax {
+            FrontMatterSyntax::Toml => '=',
+            FrontMatterSyntax::Yaml | FrontMatterSyntax::Json => ':',
+        };
+
+        self.text
+            .lines()
+            .position(|line| {
+                let Some((candidate, _)) = line.trim_start().split_once(separator) else {
+                    return false;
+                };
+                let candidate = normalized(candidate.trim().trim_matches(['"', '\'', '{']));
+                !candidate.is_empty()
+                    && (key == candidate || key.ends_with(&format!(".{}", candidate)))
+            })
+            .map(|index| self.first_line + index)
+    }
+}
+
+/// Splits a note into its raw front matter block, if it starts with one, and its body.
+///
+/// Only a delimiter on the very first line opens a block, and the block ends at the
+/// next matching delimiter line (`...` also closes YAML), so horizontal rules in the
+/// body are never mistaken for it.
+fn split_front_matter(content: &str) -> (Option<FrontMatterBlock<'_>>, &str) {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut lines = content.split_inclusive('\n');
+    let Some(opening) = lines.next() else {
+        return (None, content);
+    };
+    let (syntax, closing): (_, &[&str]) = match opening.trim_end() {
+        "---" => (FrontMatterSyntax::Yaml, &["---", "..."]),
+        "+++" => (FrontMatterSyntax::Toml, &["+++"]),
+        ";;;" => (FrontMatterSyntax::Json, &[";;;"]),
+        line if line == "{" || line.starts_with("{\"") => return split_json_object(content),
+        _ => return (None, content),
+    };
+
+    let start = opening.len();
+    let mut end = start;
+    for line in lines {
+        if closing.contains(&line.trim_end()) {
+            let block = FrontMatterBlock {
+                syntax,
+                text: &content[start..end],
+                first_line: 2,
+            };
+            return (Some(block), &content[end + line.len()..]);
+        }
+        end += line.len();
+    }
+    (None, content)
+}
+
+/// Splits off a JSON object that opens the note. The body starts on the line after
+/// the closing brace. An object that never closes is taken whole, so its parse error
+/// is reported.
+fn split_json_object(content: &str) -> (Option<FrontMatterBlock<'_>>, &str) {
+    let mut values = serde_json::Deserializer::from_str(content).into_iter::<IgnoredAny>();
+    let end = match values.next() {
+        Some(Ok(_)) => values.byte_offset(),
+        _ => content.len(),
+    };
+    let body_start = content[end..]
+        .find('\n')
+        .map_or(content.len(), |newline| end + newline + 1);
+
+    let block = FrontMatterBlock {
+        syntax: FrontMatterSyntax::Json,
+        text: &content[..end],
+        first_line: 1,
+    };
+    (Some(block), &content[body_start..])
+}
+
+/// Converts parsed TOML into the value tree the other syntaxes parse into. Dates and
+/// times are kept as text, the way YAML front matter holds them.
+fn toml_to_yaml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(text) => Value::String(text),
+        toml::Value::Integer(number) => Value::Number(number.into()),
+        toml::Value::Float(number) => Value::Number(number.into()),
+        toml::Value::Boolean(flag) => Value::Bool(flag),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => Value::Sequence(items.into_iter().map(toml_to_yaml).collect()),
+        toml::Value::Table(table) => Value::Mapping(
+            table
+                .into_iter()
+                .map(|(key, value)| (Value::String(key), toml_to_yaml(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::metric_value::MetricType;
+
+    const NOTE: &str = "---
+pages: 3 # only skimmed
+pages_read: \"12\"
+mood: >
+  tired but
+  fine
+habits:
+  reading: 20
+  workout.minutes: 45
+---
+# Morning
+
+---
+
+pages_read: 99
+";
+
+    fn parse_front_matter(content: &str) -> Result<Option<Value>, FormatError> {
+        Note::parse(content, &IngestConfig::default()).map(|note| note.front_matter)
+    }
+
+    fn integer(value: &Value) -> f64 {
+        MetricType::Integer.parse_value(value).unwrap().as_f64()
+    }
+
+    #[test]
+    fn test_parse_front_matter_ignores_body_rules() {
+        let front_matter = parse_front_matter(NOTE)
+            .expect("Failed to parse front matter")
+            .expect("Front matter should be present");
+
+        assert_eq!(
+            integer(lookup_metric(&front_matter, "pages_read").unwrap()),
+            12.0
+        );
+        assert_eq!(integer(lookup_metric(&front_matter, "pages").unwrap()), 3.0);
+    }
+
+    #[test]
+    fn test_lookup_metric_by_dotted_path() {
+        let front_matter = parse_front_matter(NOTE).unwrap().unwrap();
+
+        assert_eq!(
+            integer(lookup_metric(&front_matter, "habits.reading").unwrap()),
+            20.0
+        );
+        assert_eq!(
+            integer(lookup_metric(&front_matter, "habits.workout.minutes").unwrap()),
+            45.0
+        );
+        assert!(lookup_metric(&front_matter, "habits.writing").is_none());
+    }
+
+    #[test]
+    fn test_note_without_front_matter() {
+        let note = "# Evening\n\n---\n\npages_read: 10\n";
+        assert!(parse_front_matter(note).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_toml_and_json_front_matter() {
+        let notes = [
+            "+++\ntitle = \"Tuesday\"\ndate = 2025-10-20\n\n[habits]\nreading = 20\n+++\nBody\n",
+            ";;;\n{\n  \"date\": \"2025-10-20\",\n  \"habits\": {\n    \"reading\": 20\n  }\n}\n;;;\n",
+            "{\n  \"date\": \"2025-10-20\",\n  \"habits\": {\n    \"reading\": 20\n  }\n}\n# Body\n",
+        ];
+        let metric = MetricDefinition::new("habits.reading", MetricType::Integer);
+        let config = IngestConfig::default();
+
+        for (note, reading_line) in notes.into_iter().zip([6, 5, 4]) {
+            let entry = Markdown.parse(note, &config).unwrap();
+            let found = entry.field_values(&metric, &config).remove(0);
+            assert_eq!(found.value.unwrap().as_f64(), 20.0, "{}", note);
+            assert_eq!(found.line, Some(reading_line), "{}", note);
+            assert_eq!(
+                entry.date_field("date"),
+                NaiveDate::from_ymd_opt(2025, 10, 20)
+            );
+        }
+
+        let broken = Note::parse("+++\ntitle = \"Tuesday\"\npages =\n+++\n", &config);
+        assert_eq!(broken.err().and_then(|e| e.line), Some(3));
+        let (block, body) = split_front_matter("{\"pages\": 3}\n# Body\n");
+        assert_eq!(block.unwrap().text, "{\"pages\": 3}");
+        assert_eq!(body, "# Body\n");
+    }
+
+    #[test]
+    fn test_only_log_lines_start_with_a_time() {
+        let config = IngestConfig {
+            inline_fields: true,
+            ..Default::default()
+        };
+        let entry = Markdown
+            .parse(
+                "---\nfocus: 7:30\npages: 07:30 12\n---\n- run_km:: 07:30 5\n",
+                &config,
+            )
+            .unwrap();
+        let found = |name: &str, metric_type: MetricType| {
+            entry
+                .field_values(&MetricDefinition::new(name, metric_type), &config)
+                .remove(0)
+        };
+
+        let focus = found("focus", MetricType::Duration);
+        assert_eq!(focus.value.unwrap().as_f64(), 450.0);
+        assert_eq!(focus.time, None);
+        assert!(found("pages", MetricType::Integer).value.is_err());
+
+        let run = found("run_km", MetricType::Integer);
+        assert_eq!(run.value.unwrap().as_f64(), 5.0);
+        assert_eq!(run.time, chrono::NaiveTime::from_hms_opt(7, 30, 0));
+    }
+}
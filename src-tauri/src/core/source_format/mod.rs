@@ -0,0 +1,205 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveTime};
+use serde_yaml::Value;
+
+use crate::core::{
+    checkboxes::Checkbox,
+    metric_value::{MetricDefinition, MetricValue},
+    IngestConfig,
+};
+
+pub mod json;
+pub mod markdown;
+pub mod org;
+pub mod properties;
+
+use json::Json;
+use markdown::Markdown;
+use org::OrgMode;
+
+/// A way of keeping a journal on disk: which files hold entries, and how an entry's
+/// date and metric values are read from one.
+pub trait SourceFormat: Send + Sync {
+    /// Name the format is enabled under in settings.
+    fn name(&self) -> &'static str;
+
+    /// File extensions of the format, without the dot.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Whether the file at `path` is in this format.
+    fn matches(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                self.extensions()
+                    .iter()
+                    .any(|candidate| candidate.eq_ignore_ascii_case(extension))
+            })
+    }
+
+    /// Parses a file's content into an entry, reading only as much as `config` asks for.
+    fn parse<'a>(
+        &self,
+        content: &'a str,
+        config: &IngestConfig,
+    ) -> Result<Box<dyn SourceEntry + 'a>, FormatError>;
+}
+
+/// A parsed journal entry.
+pub trait SourceEntry {
+    /// The date the entry sets under `field`, such as a `date` front matter key or an
+    /// Org `:DATE:` property.
+    fn date_field(&self, field: &str) -> Option<NaiveDate>;
+
+    /// Every value the entry sets for a tracked metric, in the order written, or none if
+    /// it doesn't mention the metric. Within a source the metric's name is tried before
+    /// its aliases.
+    fn field_values(&self, metric: &MetricDefinition, config: &IngestConfig) -> Vec<FoundValue>;
+
+    /// The entry's checklist items, for metrics tracked by checkbox.
+    fn checkboxes(&self) -> &[Checkbox] {
+        &[]
+    }
+}
+
+/// A file that couldn't be parsed at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatError {
+    pub message: String,
+    /// 1-based line of the file, when known.
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// A tracked metric's value as found in an entry, along with the text it was parsed
+/// from so problems can be reported.
+pub struct FoundValue {
+    pub value: Result<MetricValue>,
+    pub raw: String,
+    /// 1-based line of the file, when known.
+    pub line: Option<usize>,
+    /// The time of day the value was logged at, when the entry says.
+    pub time: Option<NaiveTime>,
+}
+
+impl FoundValue {
+    /// Parses text such as `12` or `7:30`.
+    pub fn from_text(metric: &MetricDefinition, raw: &str, line: Option<usize>) -> Self {
+        FoundValue {
+            value: metric.parse_str(raw),
+            raw: raw.to_string(),
+            line,
+            time: None,
+        }
+    }
+
+    /// Parses the value of a log line, such as `12` or `07:30 12` where a leading time
+    /// of day says when the value was logged. Only log lines take a time this way, so
+    /// that a duration such as `7:30 h` elsewhere isn't mistaken for one.
+    pub fn from_log_text(metric: &MetricDefinition, raw: &str, line: Option<usize>) -> Self {
+        let (time, text) = properties::split_time(raw);
+        FoundValue {
+            value: metric.parse_str(text),
+            raw: raw.to_string(),
+            line,
+            time,
+        }
+    }
+
+    /// Parses a structured value, where a list such as `[10, 25]` holds one logged
+    /// value per item.
+    pub fn from_value(metric: &MetricDefinition, value: &Value, line: Option<usize>) -> Vec<Self> {
+        let found = |value: &Value| match value {
+            Value::String(raw) => FoundValue::from_text(metric, raw, line),
+            _ => FoundValue {
+                value: metric.parse_value(value),
+                raw: properties::raw_text(value),
+                line,
+                time: None,
+            },
+        };
+        match value {
+            Value::Sequence(items) => items.iter().map(found).collect(),
+            Value::Tagged(tagged) => FoundValue::from_value(metric, &tagged.value, line),
+            _ => vec![found(value)],
+        }
+    }
+}
+
+/// Every format that ships with the app.
+pub static BUILTIN_FORMATS: [&dyn SourceFormat; 3] = [&Markdown, &OrgMode, &Json];
+
+/// The formats enabled for ingestion. Markdown alone unless configured otherwise.
+#[derive(Clone)]
+pub struct SourceFormats(Vec<&'static dyn SourceFormat>);
+
+impl SourceFormats {
+    /// Looks the named built-in formats up. An empty list means the default.
+    pub fn from_names(names: &[String]) -> Result<Self> {
+        if names.is_empty() {
+            return Ok(SourceFormats::default());
+        }
+
+        let formats = names
+            .iter()
+            .map(|name| {
+                BUILTIN_FORMATS
+                    .iter()
+                    .find(|format| format.name() == name.trim())
+                    .copied()
+                    .ok_or_else(|| anyhow!("Unknown journal format {}", name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(SourceFormats(formats))
+    }
+
+    /// The enabled format that reads the file at `path`, if any.
+    pub fn for_path(&self, path: &Path) -> Option<&'static dyn SourceFormat> {
+        self.0.iter().find(|format| format.matches(path)).copied()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|format| format.name().to_string())
+            .collect()
+    }
+}
+
+impl Default for SourceFormats {
+    fn default() -> Self {
+        SourceFormats(vec![&Markdown])
+    }
+}
+
+impl fmt::Debug for SourceFormats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_match_by_extension() {
+        let formats =
+            SourceFormats::from_names(&["markdown".to_string(), "org".to_string()]).unwrap();
+
+        let name = |path: &str| formats.for_path(Path::new(path)).map(|f| f.name());
+        assert_eq!(name("/vault/2025-10-20.md"), Some("markdown"));
+        assert_eq!(name("/vault/2025-10-20.ORG"), Some("org"));
+        assert_eq!(name("/vault/2025-10-20.json"), None);
+        assert_eq!(SourceFormats::default().names(), vec!["markdown"]);
+        assert!(SourceFormats::from_names(&["docx".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::sync::LazyLock;
+
+use chrono::NaiveDate;
+use regex::Regex;
+
+use crate::core::{
+    checkboxes::{parse_checkboxes, Checkbox},
+    metric_value::{normalize_metric_key, MetricDefinition},
+    source_format::{
+        properties::parse_date_prefix, FormatError, FoundValue, SourceEntry, SourceFormat,
+    },
+    IngestConfig,
+};
+
+/// A property inside a drawer: `:pages_read: 12`.
+static PROPERTY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*:([^:\s]+):(?:\s+(.*?))?\s*$").expect("valid org property regex")
+});
+
+/// An in-buffer setting: `#+date: <2025-10-20 Mon>`.
+static KEYWORD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*#\+([^:\s]+):(?:\s+(.*?))?\s*$").expect("valid org keyword regex")
+});
+
+/// Org-mode dailies, as kept by org-roam or org-journal, with metrics in
+/// `:PROPERTIES:` drawers and `#+key:` settings.
+pub struct OrgMode;
+
+impl SourceFormat for OrgMode {
+    fn name(&self) -> &'static str {
+        "org"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["org"]
+    }
+
+    fn parse<'a>(
+        &self,
+        content: &'a str,
+        config: &IngestConfig,
+    ) -> Result<Box<dyn SourceEntry + 'a>, FormatError> {
+        let checkboxes = if config
+            .tracked_metrics
+            .iter()
+            .any(|metric| metric.checkbox_label.is_some())
+        {
+            parse_checkboxes(content)
+        } else {
+            Vec::new()
+        };
+
+        Ok(Box::new(OrgEntry {
+            properties: parse_properties(content),
+            checkboxes,
+        }))
+    }
+}
+
+/// A property or setting of an Org file and where it was found.
+#[derive(Debug, Clone, PartialEq)]
+struct OrgProperty {
+    key: String,
+    value: String,
+    /// 1-based line of the file.
+    line: usize,
+}
+
+struct OrgEntry {
+    properties: Vec<OrgProperty>,
+    checkboxes: Vec<Checkbox>,
+}
+
+impl OrgEntry {
+    /// Every property set under `key`, in the order written. Org property names ignore
+    /// case. With `normalize` set, `-` and `_` match too.
+    fn properties(&self, key: &str, normalize: bool) -> Vec<&OrgProperty> {
+        let normalized = normalize_metric_key(key);
+        self.properties
+            .iter()
+            .filter(|property| {
+                if normalize {
+                    normalize_metric_key(&property.key) == normalized
+                } else {
+                    property.key.eq_ignore_ascii_case(key)
+                }
+            })
+            .collect()
+    }
+}
+
+impl SourceEntry for OrgEntry {
+    /// Reads plain dates as well as active and inactive timestamps such as
+    /// `<2025-10-20 Mon>` and `[2025-10-20 Mon 07:30]`.
+    fn date_field(&self, field: &str) -> Option<NaiveDate> {
+        let value = self.properties(field, false).first()?.value.trim();
+        let value = value
+            .strip_prefix(['<', '['])
+            .and_then(|value| value.strip_suffix(['>', ']']))
+            .unwrap_or(value);
+        parse_date_prefix(value)
+    }
+
+    /// A property repeated across drawers, say under one heading per workout, gives one
+    /// value per drawer.
+    fn field_values(&self, metric: &MetricDefinition, config: &IngestConfig) -> Vec<FoundValue> {
+        metric
+            .keys()
+            .map(|key| self.properties(key, config.normalize_keys))
+            .find(|properties| !properties.is_empty())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|property| FoundValue::from_text(metric, &property.value, Some(property.line)))
+            .collect()
+    }
+
+    fn checkboxes(&self) -> &[Checkbox] {
+        &self.checkboxes
+    }
+}
+
+/// Collects the properties of every drawer in the file along with its `#+key:`
+/// settings, skipping source blocks.
+fn parse_properties(content: &str) -> Vec<OrgProperty> {
+    let mut properties = Vec::new();
+    let mut in_drawer = false;
+    let mut in_block = false;
+
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim().to_lowercase();
+        if in_block {
+            in_block = !trimmed.starts_with("#+end_");
+            continue;
+        }
+        if trimmed.starts_with("#+begin_") {
+            in_block = true;
+            continue;
+        }
+        if trimmed == ":properties:" {
+            in_drawer = true;
+            continue;
+        }
+        if trimmed == ":end:" {
+            in_drawer = false;
+            continue;
+        }
+
+        let pattern = if in_drawer { &PROPERTY } else { &KEYWORD };
+        let Some(captures) = pattern.captures(line) else {
+            continue;
+        };
+        let value = captures.get(2).map_or("", |value| value.as_str());
+        if value.is_empty() {
+            continue;
+        }
+        properties.push(OrgProperty {
+            key: captures[1].to_string(),
+            value: value.to_string(),
+            line: index + 1,
+        });
+    }
+
+    properties
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::metric_value::{MetricType, MetricValue};
+
+    const NOTE: &str = "#+title: 2025-10-20
+#+date: [2025-10-20 Mon 07:30]
+
+* Morning
+:PROPERTIES:
+:PAGES_READ: 12
+:Workout-Minutes: 45
+:ID: 1b2c
+:END:
+
+#+begin_src org
+:PROPERTIES:
+:MOOD: 1
+:END:
+#+end_src
+
+- [X] meditate
+";
+
+    #[test]
+    fn test_org_properties_and_dates() {
+        let metric = |name: &str, label: Option<&str>| MetricDefinition {
+            checkbox_label: label.map(|label| label.parse().unwrap()),
+            ..MetricDefinition::new(name, MetricType::Integer)
+        };
+        let config = IngestConfig {
+            tracked_metrics: vec![metric("meditation", Some("meditate"))],
+            normalize_keys: true,
+            ..Default::default()
+        };
+        let entry = OrgMode.parse(NOTE, &config).unwrap();
+        let value = |name: &str| {
+            entry
+                .field_values(&metric(name, None), &config)
+                .into_iter()
+                .next()
+                .map(|found| (found.value.unwrap(), found.line))
+        };
+
+        assert_eq!(
+            entry.date_field("date"),
+            NaiveDate::from_ymd_opt(2025, 10, 20)
+        );
+        assert_eq!(
+            value("pages_read"),
+            Some((MetricValue::Integer(12), Some(6)))
+        );
+        assert_eq!(
+            value("workout_minutes"),
+            Some((MetricValue::Integer(45), Some(7)))
+        );
+        assert_eq!(value("mood"), None);
+        assert!(entry.checkboxes()[0].checked);
+    }
+}
@@ -0,0 +1,96 @@
+use chrono::{NaiveDate, NaiveTime};
+use serde_yaml::Value;
+
+use crate::core::{metric_value::normalize_metric_key, read_journal::DB_DATE_FORMAT};
+
+/// Looks a tracked metric up by its exact key first, then as a dotted path
+/// (`habits.reading`) into nested maps. Keys that contain dots themselves still
+/// resolve, since every split point is tried.
+pub fn lookup_metric<'a>(properties: &'a Value, key: &str) -> Option<&'a Value> {
+    lookup_key(properties, key, false)
+}
+
+/// Like [`lookup_metric`], but with `normalize` set each key segment also matches keys
+/// that differ only in case or in `-` versus `_`.
+pub fn lookup_key<'a>(properties: &'a Value, key: &str, normalize: bool) -> Option<&'a Value> {
+    if let Some(value) = get_key(properties, key, normalize) {
+        return Some(value);
+    }
+
+    key.match_indices('.').find_map(|(i, _)| {
+        get_key(properties, &key[..i], normalize)
+            .and_then(|nested| lookup_key(nested, &key[i + 1..], normalize))
+    })
+}
+
+fn get_key<'a>(map: &'a Value, key: &str, normalize: bool) -> Option<&'a Value> {
+    if let Some(value) = map.get(key) {
+        return Some(value);
+    }
+    if !normalize {
+        return None;
+    }
+
+    let key = normalize_metric_key(key);
+    map.as_mapping()?
+        .iter()
+        .find(|(candidate, _)| {
+            candidate
+                .as_str()
+                .is_some_and(|candidate| normalize_metric_key(candidate) == key)
+        })
+        .map(|(_, value)| value)
+}
+
+/// A value as the user wrote it, near enough to show back in a diagnostic.
+pub fn raw_text(value: &Value) -> String {
+    match value {
+        Value::String(raw) => raw.clone(),
+        Value::Number(number) => number.to_string(),
+        Value::Bool(flag) => flag.to_string(),
+        Value::Null => String::new(),
+        Value::Tagged(tagged) => raw_text(&tagged.value),
+        Value::Sequence(_) | Value::Mapping(_) => serde_yaml::to_string(value)
+            .map(|raw| raw.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Reads `2025-10-20`, as well as timestamps such as `2025-10-20T07:30:00+02:00`,
+/// as a calendar date.
+pub fn value_date(value: &Value) -> Option<NaiveDate> {
+    match value {
+        Value::String(raw) => parse_date_prefix(raw),
+        Value::Tagged(tagged) => value_date(&tagged.value),
+        _ => None,
+    }
+}
+
+/// Parses a date that may be followed by a time, as in `2025-10-20 07:30`.
+pub fn parse_date_prefix(raw: &str) -> Option<NaiveDate> {
+    let (date, remainder) = NaiveDate::parse_and_remainder(raw.trim(), DB_DATE_FORMAT).ok()?;
+    if remainder.is_empty() || remainder.starts_with(['T', 't', ' ']) {
+        Some(date)
+    } else {
+        None
+    }
+}
+
+/// Splits a leading `07:30` or `07:30:15` off a value, as long as something follows it.
+pub fn split_time(raw: &str) -> (Option<NaiveTime>, &str) {
+    let raw = raw.trim();
+    let Some((first, rest)) = raw.split_once(char::is_whitespace) else {
+        return (None, raw);
+    };
+    match parse_time(first) {
+        Some(time) if !rest.trim().is_empty() => (Some(time), rest.trim()),
+        _ => (None, raw),
+    }
+}
+
+/// Reads a 24-hour time of day such as `07:30` or `18:05:10`.
+pub fn parse_time(raw: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(raw, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(raw, "%H:%M:%S"))
+        .ok()
+}
@@ -0,0 +1,408 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Condvar, Mutex, MutexGuard},
+};
+
+use serde::Serialize;
+
+/// Where the sync workers are at, since they were last idle or for one resync.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    pub processed: usize,
+    pub failed: usize,
+    /// Files queued or being read.
+    pub remaining: usize,
+}
+
+impl SyncStatus {
+    /// How far along the work is, as a whole percentage.
+    pub fn percent(&self) -> f32 {
+        let done = self.processed + self.failed;
+        let total = done + self.remaining;
+        if total == 0 {
+            100.0
+        } else {
+            (done as f32 / total as f32 * 100.0).round()
+        }
+    }
+}
+
+/// What a resync re-reads, which decides the earlier resyncs it replaces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResyncScope {
+    /// Every enabled root.
+    All,
+    /// Some roots, by path.
+    Roots(Vec<String>),
+}
+
+impl ResyncScope {
+    pub fn roots(mut paths: Vec<String>) -> Self {
+        paths.sort();
+        paths.dedup();
+        ResyncScope::Roots(paths)
+    }
+
+    /// Whether a resync of this scope re-reads everything one of `other` would. A full
+    /// resync replaces any other; a resync of some roots only replaces one of the
+    /// same roots.
+    fn supersedes(&self, other: &ResyncScope) -> bool {
+        *self == ResyncScope::All || self == other
+    }
+}
+
+/// A file handed to a sync worker.
+#[derive(Debug)]
+pub struct SyncJob {
+    pub path: String,
+    /// The resyncs waiting on this read. One started after the job was taken reads the
+    /// file again rather than counting a read that may predate its settings.
+    resyncs: Vec<u64>,
+}
+
+/// A resync's own share of the queue.
+#[derive(Debug)]
+struct Resync {
+    scope: ResyncScope,
+    /// Its files that haven't been read since it started.
+    pending: HashSet<String>,
+    processed: usize,
+    failed: usize,
+}
+
+impl Resync {
+    fn status(&self) -> SyncStatus {
+        SyncStatus {
+            processed: self.processed,
+            failed: self.failed,
+            remaining: self.pending.len(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    /// Files the watcher saw change, read before any rescan.
+    live: VecDeque<String>,
+    /// Files of the running rescans and reconciles, each queued once.
+    bulk: VecDeque<String>,
+    /// Files a worker is reading. A file saved again meanwhile stays queued until the
+    /// read finishes, so two reads of it never race to be stored.
+    in_flight: HashSet<String>,
+    next_id: u64,
+    running: HashMap<u64, Resync>,
+    /// Resyncs that read all their files, until their waiter collects the outcome.
+    finished: HashMap<u64, SyncStatus>,
+    superseded: HashSet<u64>,
+    /// Counted since the workers were last idle.
+    processed: usize,
+    failed: usize,
+}
+
+impl QueueState {
+    fn status(&self) -> SyncStatus {
+        SyncStatus {
+            processed: self.processed,
+            failed: self.failed,
+            remaining: self.live.len() + self.bulk.len() + self.in_flight.len(),
+        }
+    }
+
+    /// The first queued file no worker is reading, live changes first.
+    fn take_next(&mut self) -> Option<String> {
+        let in_flight = &self.in_flight;
+        for queue in [&mut self.live, &mut self.bulk] {
+            if let Some(index) = queue.iter().position(|path| !in_flight.contains(path)) {
+                return queue.remove(index);
+            }
+        }
+        None
+    }
+
+    /// Queues files for a rescan, skipping those already waiting to be read.
+    fn queue_bulk(&mut self, paths: impl IntoIterator<Item = String>) {
+        let mut queued: HashSet<String> =
+            self.live.iter().chain(self.bulk.iter()).cloned().collect();
+        for path in paths {
+            if queued.insert(path.clone()) {
+                self.bulk.push_back(path);
+            }
+        }
+    }
+}
+
+/// The files waiting for the sync workers. A path is queued at most once, and files the
+/// watcher reports go ahead of rescans. Rescans run side by side, except that starting
+/// one drops what is left of those it supersedes.
+#[derive(Debug, Default)]
+pub struct SyncQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+impl SyncQueue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queues a file that changed while watching, ahead of any rescan.
+    pub fn push_live(&self, path: String) {
+        let mut state = self.lock();
+        if state.live.contains(&path) {
+            return;
+        }
+        state.bulk.retain(|queued| *queued != path);
+        state.live.push_back(path);
+        self.changed.notify_all();
+    }
+
+    /// Starts a rescan of `paths`. Running rescans of a scope it covers are cancelled
+    /// and their unread files dropped, unless another rescan still needs them; the rest
+    /// carry on alongside it. Returns the rescan's id for [`Self::wait_for_resync`].
+    pub fn start_resync(&self, scope: ResyncScope, paths: Vec<String>) -> u64 {
+        let mut state = self.lock();
+        state.next_id += 1;
+        let id = state.next_id;
+
+        let superseded = state
+            .running
+            .iter()
+            .filter(|(_, resync)| scope.supersedes(&resync.scope))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        let mut dropped = HashSet::new();
+        for old in superseded {
+            if let Some(resync) = state.running.remove(&old) {
+                dropped.extend(resync.pending);
+            }
+            state.superseded.insert(old);
+        }
+
+        let pending: HashSet<String> = paths.iter().cloned().collect();
+        dropped.retain(|path| {
+            !pending.contains(path)
+                && !state
+                    .running
+                    .values()
+                    .any(|resync| resync.pending.contains(path))
+        });
+        state.bulk.retain(|path| !dropped.contains(path));
+        state.queue_bulk(paths);
+
+        if pending.is_empty() {
+            state.finished.insert(id, SyncStatus::default());
+        } else {
+            state.running.insert(
+                id,
+                Resync {
+                    scope,
+                    pending,
+                    processed: 0,
+                    failed: 0,
+                },
+            );
+        }
+        self.changed.notify_all();
+        id
+    }
+
+    /// Queues files to read alongside whatever is running, without cancelling anything.
+    pub fn enqueue(&self, paths: Vec<String>) {
+        let mut state = self.lock();
+        state.queue_bulk(paths);
+        self.changed.notify_all();
+    }
+
+    /// Waits for the next file to read. Files already being read wait their turn.
+    pub fn next_job(&self) -> SyncJob {
+        let mut state = self.lock();
+        loop {
+            if let Some(path) = state.take_next() {
+                state.in_flight.insert(path.clone());
+                let resyncs = state
+                    .running
+                    .iter()
+                    .filter(|(_, resync)| resync.pending.contains(&path))
+                    .map(|(id, _)| *id)
+                    .collect();
+                return SyncJob { path, resyncs };
+            }
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Records that a job was read, or failed to be, and returns the new status.
+    pub fn finish(&self, job: SyncJob, ok: bool) -> SyncStatus {
+        let mut state = self.lock();
+        state.in_flight.remove(&job.path);
+        if ok {
+            state.processed += 1;
+        } else {
+            state.failed += 1;
+        }
+
+        for id in job.resyncs {
+            let Some(resync) = state.running.get_mut(&id) else {
+                continue;
+            };
+            if !resync.pending.remove(&job.path) {
+                continue;
+            }
+            if ok {
+                resync.processed += 1;
+            } else {
+                resync.failed += 1;
+            }
+            if resync.pending.is_empty() {
+                let status = resync.status();
+                state.running.remove(&id);
+                state.finished.insert(id, status);
+            }
+        }
+
+        let status = state.status();
+        if status.remaining == 0 {
+            state.processed = 0;
+            state.failed = 0;
+        }
+        self.changed.notify_all();
+        status
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        self.lock().status()
+    }
+
+    /// Waits until every file of the rescan `id` has been read, and returns how that
+    /// went. Returns `None` if a newer rescan superseded it first.
+    pub fn wait_for_resync(&self, id: u64) -> Option<SyncStatus> {
+        let mut state = self.lock();
+        loop {
+            if state.superseded.remove(&id) {
+                return None;
+            }
+            if let Some(status) = state.finished.remove(&id) {
+                return Some(status);
+            }
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn test_sync_queue_order_and_progress() {
+        let queue = SyncQueue::default();
+        let daily = || ResyncScope::roots(vec!["/daily".to_string()]);
+        let first = queue.start_resync(daily(), paths(&["a.md", "b.md", "c.md", "a.md"]));
+        queue.push_live("c.md".to_string());
+        queue.push_live("c.md".to_string());
+
+        // The live change goes first and isn't read again by the rescan.
+        let job = queue.next_job();
+        assert_eq!(job.path, "c.md");
+        let status = queue.finish(job, true);
+        assert_eq!(status.processed, 1);
+        assert_eq!(status.remaining, 2);
+
+        let stale = queue.next_job();
+        assert_eq!(stale.path, "a.md");
+
+        // A newer rescan of the same root drops what the first hadn't got to, and a
+        // job of the first finishing late doesn't count toward it.
+        let second = queue.start_resync(daily(), paths(&["d.md"]));
+        assert_eq!(queue.wait_for_resync(first), None);
+        queue.finish(stale, false);
+        assert_eq!(queue.status().remaining, 1);
+
+        let job = queue.next_job();
+        assert_eq!(job.path, "d.md");
+        let status = queue.finish(job, false);
+        assert_eq!(status.failed, 2);
+        assert_eq!(status.percent(), 100.0);
+        assert_eq!(
+            queue.wait_for_resync(second),
+            Some(SyncStatus {
+                processed: 0,
+                failed: 1,
+                remaining: 0
+            })
+        );
+        assert_eq!(queue.status(), SyncStatus::default());
+    }
+
+    #[test]
+    fn test_resyncs_of_other_roots_run_side_by_side() {
+        let queue = SyncQueue::default();
+        queue.enqueue(paths(&["changed.md"]));
+        let daily = queue.start_resync(
+            ResyncScope::roots(vec!["/daily".to_string()]),
+            paths(&["/daily/a.md", "/daily/b.md"]),
+        );
+        let work = queue.start_resync(
+            ResyncScope::roots(vec!["/work".to_string()]),
+            paths(&["/work/a.md"]),
+        );
+
+        // Neither the other root's rescan nor the reconciled file is dropped.
+        let mut read = Vec::new();
+        while queue.status().remaining > 0 {
+            let job = queue.next_job();
+            read.push(job.path.clone());
+            queue.finish(job, true);
+        }
+        assert_eq!(
+            read,
+            paths(&["changed.md", "/daily/a.md", "/daily/b.md", "/work/a.md"])
+        );
+        assert_eq!(queue.wait_for_resync(daily).map(|s| s.processed), Some(2));
+        assert_eq!(queue.wait_for_resync(work).map(|s| s.processed), Some(1));
+
+        // A full resync replaces a resync of some roots.
+        let some = queue.start_resync(
+            ResyncScope::roots(vec!["/daily".to_string()]),
+            paths(&["/daily/a.md"]),
+        );
+        let all = queue.start_resync(ResyncScope::All, paths(&["/daily/a.md", "/work/a.md"]));
+        assert_eq!(queue.wait_for_resync(some), None);
+        assert_eq!(queue.status().remaining, 2);
+        for _ in 0..2 {
+            let job = queue.next_job();
+            queue.finish(job, true);
+        }
+        assert_eq!(queue.wait_for_resync(all).map(|s| s.processed), Some(2));
+    }
+
+    #[test]
+    fn test_file_saved_while_being_read_waits_for_the_read() {
+        let queue = SyncQueue::default();
+        queue.push_live("a.md".to_string());
+        let reading = queue.next_job();
+        assert_eq!(reading.path, "a.md");
+
+        // Saved again mid-read, then another note saved.
+        queue.push_live("a.md".to_string());
+        queue.push_live("b.md".to_string());
+        assert_eq!(queue.status().remaining, 3);
+
+        // The second read of a.md isn't handed out while the first is running.
+        let job = queue.next_job();
+        assert_eq!(job.path, "b.md");
+        queue.finish(job, true);
+
+        queue.finish(reading, true);
+        let job = queue.next_job();
+        assert_eq!(job.path, "a.md");
+        let status = queue.finish(job, true);
+        assert_eq!(status.processed, 3);
+        assert_eq!(status.remaining, 0);
+    }
+}
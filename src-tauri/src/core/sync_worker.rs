@@ -1,53 +1,179 @@
-use std::sync::{mpsc, Arc, Mutex};
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
 
-use tauri::{AppHandle, Manager};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
 
 use crate::{
-    core::{get_tracked_metrics_from_db, read_journal::read_front_matter},
+    core::{
+        change_events::{emit_file_ingested, emit_metrics_changed, MetricChanges},
+        ingest_cache::IngestConfigCache,
+        read_journal::{parse_journal_file, write_file_updates, FileUpdate},
+        sync_queue::{SyncJob, SyncQueue, SyncStatus},
+    },
     DbConnection,
 };
 
-const WORKER_CHANNEL_CAPACITY: usize = 4;
+const WORKER_COUNT: usize = 4;
+
+/// The most parsed files the writer stores in one transaction.
+pub const MAX_BATCH_SIZE: usize = 200;
 
-pub fn setup_sync_worker(app_handle: AppHandle) -> mpsc::SyncSender<String> {
-    let (tx, rx) = mpsc::sync_channel::<String>(WORKER_CHANNEL_CAPACITY);
-    let rx = Arc::new(Mutex::new(rx));
+/// Progress is reported at most this often, besides when the queue runs dry.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
 
-    for _ in 0..WORKER_CHANNEL_CAPACITY {
-        let rx = Arc::clone(&rx);
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncProgress {
+    sync_progress: f32,
+    processed: usize,
+    failed: usize,
+    remaining: usize,
+}
+
+impl From<SyncStatus> for SyncProgress {
+    fn from(status: SyncStatus) -> Self {
+        SyncProgress {
+            sync_progress: status.percent(),
+            processed: status.processed,
+            failed: status.failed,
+            remaining: status.remaining,
+        }
+    }
+}
+
+/// Starts the threads that read the files queued in the managed [`SyncQueue`]. Files
+/// are parsed in parallel and handed to a single writer, which stores them in batches.
+pub fn setup_sync_worker(app_handle: AppHandle) {
+    let (parsed_tx, parsed_rx) = mpsc::sync_channel(MAX_BATCH_SIZE * 2);
+    for _ in 0..WORKER_COUNT {
         let app = app_handle.clone();
-        std::thread::spawn(move || loop {
-            let msg = {
-                let lock = rx.lock().unwrap();
-                lock.recv()
-            };
+        let parsed_tx = parsed_tx.clone();
+        std::thread::spawn(move || {
+            let queue = app.state::<SyncQueue>();
+            let configs = app.state::<IngestConfigCache>();
             let db = app.state::<DbConnection>();
-            match msg {
-                Ok(file_path) => {
-                    let tracked_metrics = get_tracked_metrics_from_db(&db);
-                    let needed_metrics = match tracked_metrics {
-                        Ok(metrics) => metrics,
-                        Err(e) => {
-                            eprintln!("Error fetching tracked metrics: {}", e);
-                            continue;
-                        }
-                    };
-                    if needed_metrics.is_empty() {
-                        continue;
-                    }
-                    match read_front_matter(&file_path, &needed_metrics, &db) {
-                        Ok(_) => (),
-                        Err(e) => {
-                            eprintln!("Error processing {}: {}", file_path, e);
-                        }
-                    };
-                }
-                Err(e) => {
-                    eprintln!("Worker thread error: {}", e);
+            loop {
+                let job = queue.next_job();
+                let parsed = parse_file(&job.path, &configs, &db);
+                if parsed_tx.send((job, parsed)).is_err() {
                     break;
                 }
             }
         });
     }
-    tx
+
+    std::thread::spawn(move || {
+        let queue = app_handle.state::<SyncQueue>();
+        let mut last_progress = Instant::now();
+        while let Ok(first) = parsed_rx.recv() {
+            // Whatever the parsers got through while the last batch was written.
+            let mut batch = vec![first];
+            while batch.len() < MAX_BATCH_SIZE {
+                match parsed_rx.try_recv() {
+                    Ok(parsed) => batch.push(parsed),
+                    Err(_) => break,
+                }
+            }
+
+            let mut status = SyncStatus::default();
+            for (job, ok) in write_batch(batch, &app_handle) {
+                status = queue.finish(job, ok);
+            }
+            if status.remaining == 0 || last_progress.elapsed() >= PROGRESS_INTERVAL {
+                last_progress = Instant::now();
+                if let Err(e) = app_handle.emit("sync-progress", SyncProgress::from(status)) {
+                    eprintln!("Failed to emit progress event: {}", e);
+                }
+            }
+        }
+    });
+}
+
+type Parsed = (SyncJob, Result<Option<FileUpdate>, anyhow::Error>);
+
+fn parse_file(
+    file_path: &str,
+    configs: &IngestConfigCache,
+    db: &DbConnection,
+) -> Result<Option<FileUpdate>, anyhow::Error> {
+    let config = match configs.for_file(db, file_path)? {
+        Some(config) => config,
+        // The file's root was removed or disabled after it was queued.
+        None => return Ok(None),
+    };
+    if config.tracked_metrics.is_empty() {
+        return Ok(None);
+    }
+    parse_journal_file(file_path, &config, db)
+}
+
+/// Stores a batch of parsed files in one transaction and returns each job with whether
+/// it succeeded. If the batch can't be stored, its files are stored one at a time so
+/// one bad file doesn't fail the rest. The frontend is told which files were ingested
+/// and which metrics changed.
+fn write_batch(batch: Vec<Parsed>, app_handle: &AppHandle) -> Vec<(SyncJob, bool)> {
+    let db = app_handle.state::<DbConnection>();
+    let mut jobs = Vec::with_capacity(batch.len());
+    let mut updates = Vec::with_capacity(batch.len());
+    for (job, parsed) in batch {
+        match parsed {
+            Ok(update) => {
+                updates.extend(update);
+                jobs.push((job, true));
+            }
+            Err(e) => {
+                eprintln!("Error processing {}: {}", job.path, e);
+                updates.push(FileUpdate::unreadable(&job.path, &e));
+                jobs.push((job, false));
+            }
+        }
+    }
+
+    let (written, changes) = match write_file_updates(&db, &updates) {
+        Ok(changes) => (updates, changes),
+        Err(e) => {
+            eprintln!("Failed to store a batch of {} files: {}", updates.len(), e);
+            write_each(updates, &mut jobs, &db)
+        }
+    };
+
+    for file in written.iter().filter_map(FileUpdate::ingested) {
+        emit_file_ingested(app_handle, file);
+    }
+    emit_metrics_changed(app_handle, &changes);
+    jobs
+}
+
+/// Stores files one at a time, marking the jobs of those that fail. Returns the files
+/// that were stored and the metrics they changed.
+fn write_each(
+    updates: Vec<FileUpdate>,
+    jobs: &mut [(SyncJob, bool)],
+    db: &DbConnection,
+) -> (Vec<FileUpdate>, MetricChanges) {
+    let mut written = Vec::with_capacity(updates.len());
+    let mut changes = MetricChanges::default();
+    for update in updates {
+        match write_file_updates(db, std::slice::from_ref(&update)) {
+            Ok(file_changes) => {
+                changes.extend(file_changes);
+                written.push(update);
+            }
+            Err(e) => {
+                eprintln!("Error processing {}: {}", update.path, e);
+                if let Some((_, ok)) = jobs.iter_mut().find(|(job, _)| job.path == update.path) {
+                    *ok = false;
+                }
+                // Leave the reason where read failures are reported.
+                let unreadable = FileUpdate::unreadable(&update.path, &e);
+                if let Err(e) = write_file_updates(db, &[unreadable]) {
+                    eprintln!("Failed to record the error for {}: {}", update.path, e);
+                }
+            }
+        }
+    }
+    (written, changes)
 }
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+/// What a unit measures. Values only convert between units of the same dimension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Distance,
+    Mass,
+    Volume,
+    Time,
+    Energy,
+}
+
+/// A unit values can be converted to and from.
+#[derive(Debug, PartialEq)]
+pub struct KnownUnit {
+    /// How the unit is shown and stored.
+    pub symbol: &'static str,
+    /// Other spellings accepted after a value, compared ignoring case.
+    pub aliases: &'static [&'static str],
+    pub dimension: Dimension,
+    /// How many of the dimension's base unit (metres, grams, millilitres, seconds or
+    /// kilocalories) one of this unit is.
+    pub factor: f64,
+}
+
+static KNOWN_UNITS: &[KnownUnit] = &[
+    KnownUnit {
+        symbol: "m",
+        aliases: &["meter", "meters", "metre", "metres"],
+        dimension: Dimension::Distance,
+        factor: 1.0,
+    },
+    KnownUnit {
+        symbol: "km",
+        aliases: &["kilometer", "kilometers", "kilometre", "kilometres"],
+        dimension: Dimension::Distance,
+        factor: 1000.0,
+    },
+    KnownUnit {
+        symbol: "mi",
+        aliases: &["mile", "miles"],
+        dimension: Dimension::Distance,
+        factor: 1609.344,
+    },
+    KnownUnit {
+        symbol: "ft",
+        aliases: &["foot", "feet"],
+        dimension: Dimension::Distance,
+        factor: 0.3048,
+    },
+    KnownUnit {
+        symbol: "g",
+        aliases: &["gram", "grams"],
+        dimension: Dimension::Mass,
+        factor: 1.0,
+    },
+    KnownUnit {
+        symbol: "kg",
+        aliases: &["kilo", "kilos", "kilogram", "kilograms"],
+        dimension: Dimension::Mass,
+        factor: 1000.0,
+    },
+    KnownUnit {
+        symbol: "lb",
+        aliases: &["lbs", "pound", "pounds"],
+        dimension: Dimension::Mass,
+        factor: 453.592_37,
+    },
+    KnownUnit {
+        symbol: "ml",
+        aliases: &["millilitre", "millilitres", "milliliter", "milliliters"],
+        dimension: Dimension::Volume,
+        factor: 1.0,
+    },
+    KnownUnit {
+        symbol: "l",
+        aliases: &["litre", "litres", "liter", "liters"],
+        dimension: Dimension::Volume,
+        factor: 1000.0,
+    },
+    KnownUnit {
+        symbol: "s",
+        aliases: &["sec", "secs", "second", "seconds"],
+        dimension: Dimension::Time,
+        factor: 1.0,
+    },
+    KnownUnit {
+        symbol: "min",
+        aliases: &["mins", "minute", "minutes"],
+        dimension: Dimension::Time,
+        factor: 60.0,
+    },
+    KnownUnit {
+        symbol: "h",
+        aliases: &["hr", "hrs", "hour", "hours"],
+        dimension: Dimension::Time,
+        factor: 3600.0,
+    },
+    KnownUnit {
+        symbol: "kcal",
+        aliases: &["cal", "calories"],
+        dimension: Dimension::Energy,
+        factor: 1.0,
+    },
+    KnownUnit {
+        symbol: "kJ",
+        aliases: &["kilojoule", "kilojoules"],
+        dimension: Dimension::Energy,
+        factor: 0.239_006,
+    },
+];
+
+impl KnownUnit {
+    fn lookup(name: &str) -> Option<&'static KnownUnit> {
+        KNOWN_UNITS.iter().find(|unit| unit.is_named(name))
+    }
+
+    fn is_named(&self, name: &str) -> bool {
+        self.symbol.eq_ignore_ascii_case(name)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A number followed by a unit: `5km`, `3 mi` or `12 pages`.
+static SUFFIXED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^([-+]?(?:\d+(?:\.\d*)?|\.\d+))\s*([^\d\s.+-].*?)$").expect("valid unit regex")
+});
+
+/// The unit a metric is measured in. Known units convert values logged in another unit
+/// of the same kind, so `3mi` is stored as 4.83 for a metric in `km`. Any other unit,
+/// such as `pages`, is a label that values may repeat but can't be converted to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unit {
+    Known(&'static KnownUnit),
+    Label(String),
+}
+
+impl Unit {
+    pub fn symbol(&self) -> &str {
+        match self {
+            Unit::Known(unit) => unit.symbol,
+            Unit::Label(label) => label,
+        }
+    }
+
+    /// Reads a value written with a unit, such as `3 mi`, in this unit. `None` means
+    /// the value has no unit and is taken as it is.
+    pub fn convert(&self, raw: &str) -> Result<Option<f64>> {
+        let Some(captures) = SUFFIXED.captures(raw.trim()) else {
+            return Ok(None);
+        };
+        let number = captures[1]
+            .parse::<f64>()
+            .map_err(|_| anyhow!("'{}' is not a number", &captures[1]))?;
+        let suffix = captures[2].trim();
+
+        let converted = match self {
+            Unit::Known(unit) if unit.is_named(suffix) => Some(number),
+            Unit::Known(unit) => KnownUnit::lookup(suffix)
+                .filter(|from| from.dimension == unit.dimension)
+                .map(|from| number * from.factor / unit.factor),
+            Unit::Label(label) if label.eq_ignore_ascii_case(suffix) => Some(number),
+            Unit::Label(_) => None,
+        };
+        converted
+            .map(Some)
+            .ok_or_else(|| anyhow!("'{}' can't be converted to {}", raw.trim(), self))
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(anyhow!("Unit is empty"));
+        }
+        if s.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(anyhow!("Unit {} starts with a number", s));
+        }
+        Ok(match KnownUnit::lookup(s) {
+            Some(unit) => Unit::Known(unit),
+            None => Unit::Label(s.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_conversion() {
+        let km: Unit = "km".parse().unwrap();
+        let convert = |raw: &str| {
+            km.convert(raw)
+                .unwrap()
+                .map(|n| (n * 100.0).round() / 100.0)
+        };
+
+        assert_eq!(convert("5km"), Some(5.0));
+        assert_eq!(convert("3 mi"), Some(4.83));
+        assert_eq!(convert("800 M"), Some(0.8));
+        assert_eq!(convert("5"), None);
+        assert!(km.convert("3 lbs").is_err());
+
+        let pages: Unit = "pages".parse().unwrap();
+        assert_eq!(pages, Unit::Label("pages".to_string()));
+        assert_eq!(pages.convert("12 pages").unwrap(), Some(12.0));
+        assert!(pages.convert("12 chapters").is_err());
+        assert_eq!("Miles".parse::<Unit>().unwrap().symbol(), "mi");
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::Local;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+
+use crate::{core::read_journal::DB_DATE_TIME_FORMAT, DbConnection};
+
+/// A problem found while ingesting a journal file, such as a value that doesn't parse
+/// as its metric's type or a file whose date can't be worked out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestDiagnostic {
+    pub file_path: String,
+    /// 1-based line in the file, when the problem can be pinned to one.
+    pub line: Option<usize>,
+    pub metric: Option<String>,
+    /// The text that failed to parse.
+    pub raw: Option<String>,
+    pub reason: String,
+}
+
+impl IngestDiagnostic {
+    /// A problem with the file as a whole rather than with one value.
+    pub fn for_file(file_path: &str, reason: impl Into<String>) -> Self {
+        IngestDiagnostic {
+            file_path: file_path.to_string(),
+            line: None,
+            metric: None,
+            raw: None,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Replaces the diagnostics stored for a file with the ones from its latest ingest, so
+/// a file that now ingests cleanly has none left. Runs on the caller's connection so it
+/// commits along with the file's rows.
+pub fn replace_file_diagnostics(
+    conn: &Connection,
+    file_path: &str,
+    diagnostics: &[IngestDiagnostic],
+) -> Result<(), anyhow::Error> {
+    conn.prepare_cached("DELETE FROM ingest_diagnostics WHERE file_path = ?1")?
+        .execute([file_path])?;
+    for diagnostic in diagnostics {
+        add_diagnostic(conn, diagnostic)?;
+    }
+    Ok(())
+}
+
+/// Stores one diagnostic on the caller's connection.
+pub fn add_diagnostic(
+    conn: &Connection,
+    diagnostic: &IngestDiagnostic,
+) -> Result<(), anyhow::Error> {
+    let created_at = Local::now().format(DB_DATE_TIME_FORMAT).to_string();
+    conn.prepare_cached(
+        "INSERT INTO ingest_diagnostics (file_path, line, metric, raw, reason, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?
+    .execute(params![
+        diagnostic.file_path,
+        diagnostic.line.map(|line| line as i64),
+        diagnostic.metric,
+        diagnostic.raw,
+        diagnostic.reason,
+        created_at
+    ])?;
+    Ok(())
+}
+
+pub fn get_ingest_diagnostics(db: &DbConnection) -> Result<Vec<IngestDiagnostic>, anyhow::Error> {
+    let conn = db.read()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT file_path, line, metric, raw, reason FROM ingest_diagnostics ORDER BY file_path, line, id",
+    )?;
+    let diagnostics = stmt
+        .query_map([], |row| {
+            Ok(IngestDiagnostic {
+                file_path: row.get(0)?,
+                line: row.get::<_, Option<i64>>(1)?.map(|line| line as usize),
+                metric: row.get(2)?,
+                raw: row.get(3)?,
+                reason: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+    Ok(diagnostics)
+}
@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use rusqlite::params;
+use serde::Serialize;
+
+use crate::{core::change_events::RemovedFiles, db::utils::delete_file_rows_where, DbConnection};
+
+/// A folder of journal entries. Roots without their own filename pattern use the
+/// global one from settings.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalRoot {
+    pub path: String,
+    pub filename_pattern: Option<String>,
+    pub enabled: bool,
+}
+
+impl JournalRoot {
+    pub fn contains(&self, file_path: &Path) -> bool {
+        file_path.starts_with(&self.path)
+    }
+}
+
+pub fn get_journal_roots(db: &DbConnection) -> Result<Vec<JournalRoot>, anyhow::Error> {
+    let conn = db.read()?;
+
+    let mut stmt =
+        conn.prepare("SELECT path, filename_pattern, enabled FROM journal_roots ORDER BY path")?;
+    let roots = stmt
+        .query_map([], |row| {
+            Ok(JournalRoot {
+                path: row.get(0)?,
+                filename_pattern: row.get(1)?,
+                enabled: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+    Ok(roots)
+}
+
+pub fn get_enabled_journal_roots(db: &DbConnection) -> Result<Vec<JournalRoot>, anyhow::Error> {
+    let mut roots = get_journal_roots(db)?;
+    roots.retain(|root| root.enabled);
+    Ok(roots)
+}
+
+pub fn get_journal_root(
+    db: &DbConnection,
+    path: &str,
+) -> Result<Option<JournalRoot>, anyhow::Error> {
+    Ok(get_journal_roots(db)?
+        .into_iter()
+        .find(|root| root.path == path))
+}
+
+pub fn upsert_journal_root(db: &DbConnection, root: &JournalRoot) -> Result<(), anyhow::Error> {
+    let conn = db.write()?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO journal_roots (path, filename_pattern, enabled) VALUES (?1, ?2, ?3)",
+        params![root.path, root.filename_pattern, root.enabled],
+    )?;
+    Ok(())
+}
+
+pub fn delete_journal_root(db: &DbConnection, path: &str) -> Result<(), anyhow::Error> {
+    let conn = db.write()?;
+
+    conn.execute("DELETE FROM journal_roots WHERE path = ?1", [path])?;
+    Ok(())
+}
+
+/// Drops the rows of the files a removed or disabled root owned, leaving those that an
+/// enabled root nested inside it still reads. Files an enclosing root takes over are
+/// dropped too, so a resync of that root reads them with its own settings.
+pub fn delete_root_file_rows(db: &DbConnection, path: &str) -> Result<RemovedFiles, anyhow::Error> {
+    let root = Path::new(path);
+    let nested = get_enabled_journal_roots(db)?
+        .into_iter()
+        .filter(|other| other.path != path && Path::new(&other.path).starts_with(root))
+        .collect::<Vec<_>>();
+    delete_file_rows_where(db, |file_path| {
+        file_path.starts_with(root) && !nested.iter().any(|other| other.contains(file_path))
+    })
+}
+
+/// The enabled roots that `path` lies inside, which read its files once it is gone.
+pub fn get_enclosing_journal_roots(
+    db: &DbConnection,
+    path: &str,
+) -> Result<Vec<JournalRoot>, anyhow::Error> {
+    let mut roots = get_enabled_journal_roots(db)?;
+    roots.retain(|root| root.path != path && root.contains(Path::new(path)));
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_root(db: &DbConnection, path: &str) {
+        upsert_journal_root(
+            db,
+            &JournalRoot {
+                path: path.to_string(),
+                filename_pattern: None,
+                enabled: true,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_removing_a_root_keeps_the_files_of_roots_nested_in_it() {
+        let db = DbConnection::open_in_memory().unwrap();
+        add_root(&db, "/journal");
+        add_root(&db, "/journal/work");
+        for file_path in ["/journal/2025-10-20.md", "/journal/work/2025-10-20.md"] {
+            db.write()
+                .unwrap()
+                .execute(
+                    "INSERT INTO metrics (file_path, name, value, date) VALUES (?1, 'pages_read', 12, '2025-10-20')",
+                    [file_path],
+                )
+                .unwrap();
+        }
+
+        delete_journal_root(&db, "/journal").unwrap();
+        let removed = delete_root_file_rows(&db, "/journal").unwrap();
+
+        assert_eq!(removed.paths, vec!["/journal/2025-10-20.md".to_string()]);
+        let conn = db.read().unwrap();
+        let kept: String = conn
+            .query_row("SELECT file_path FROM metrics", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(kept, "/journal/work/2025-10-20.md");
+    }
+}
@@ -1,11 +1,31 @@
+use std::collections::{BTreeMap, HashMap};
+
 use anyhow::Context;
-use chrono::{Datelike, Days, Local, NaiveDate, Weekday};
+use chrono::{Datelike, Days, Local, NaiveDate, NaiveTime, Weekday};
 use rusqlite::params;
+use serde::Serialize;
 
-use crate::{core::read_journal::DB_DATE_FORMAT, DbConnection};
+use crate::{
+    core::{
+        change_events::MetricChanges,
+        derived::{derive_values, DerivedMetric},
+        metric_value::{Aggregation, MetricType, TimedValue},
+        period::{Granularity, Period},
+        read_journal::{DB_DATE_FORMAT, DB_TIME_FORMAT},
+    },
+    db::{
+        diagnostics::{add_diagnostic, IngestDiagnostic},
+        utils::{add_metric_dates, get_metric_type, get_metric_units},
+    },
+    DbConnection,
+};
 
-pub fn get_weekly_metric_avg(db: &DbConnection, habit_name: &str) -> Result<i32, anyhow::Error> {
-    let conn = db.lock().unwrap();
+pub fn get_weekly_metric_avg(
+    db: &DbConnection,
+    habit_name: &str,
+    metric_type: MetricType,
+) -> Result<f64, anyhow::Error> {
+    let conn = db.read()?;
     let now = Local::now();
     let today = now.date_naive();
     let week = today.week(Weekday::Sun);
@@ -15,23 +35,31 @@ pub fn get_weekly_metric_avg(db: &DbConnection, habit_name: &str) -> Result<i32,
     let mut query_stmt = conn
         .prepare(
             "
-        SELECT round(AVG(value))
+        SELECT round(AVG(value), ?4)
         FROM metrics
         WHERE name = ?1
+        AND period = 'day'
         AND date between ?2 AND  ?3
         ",
         )
         .with_context(|| "Failed to prepare SQL statement")?;
     query_stmt
-        .query_one(params![habit_name, start_date, end_date], |row| {
-            let avg: Option<f32> = row.get(0)?;
-            Ok(avg.unwrap_or(0.0) as i32)
-        })
+        .query_one(
+            params![habit_name, start_date, end_date, metric_type.precision()],
+            |row| {
+                let avg: Option<f64> = row.get(0)?;
+                Ok(avg.unwrap_or(0.0))
+            },
+        )
         .map_err(|e| anyhow::anyhow!(e))
 }
 
-pub fn get_monthly_metric_total(db: &DbConnection, habit_name: &str) -> Result<u32, anyhow::Error> {
-    let conn = db.lock().unwrap();
+pub fn get_monthly_metric_total(
+    db: &DbConnection,
+    habit_name: &str,
+    metric_type: MetricType,
+) -> Result<f64, anyhow::Error> {
+    let conn = db.read()?;
     let today = Local::now().date_naive();
 
     let start_of_month = today
@@ -50,15 +78,17 @@ pub fn get_monthly_metric_total(db: &DbConnection, habit_name: &str) -> Result<u
         .ok_or_else(|| anyhow::anyhow!("Failed to calculate end of month"))?;
 
     let mut query_stmt = conn
-        .prepare(
+        .prepare(&format!(
             "
-        SELECT SUM(value)
+        SELECT round({}, ?4)
         FROM metrics
         WHERE name = ?1
+        AND period = 'day'
         AND date >= ?2
         AND date <= ?3
         ",
-        )
+            metric_type.total_sql()
+        ))
         .with_context(|| "Failed to prepare SQL statement")?;
 
     query_stmt
@@ -66,12 +96,334 @@ pub fn get_monthly_metric_total(db: &DbConnection, habit_name: &str) -> Result<u
             params![
                 habit_name,
                 start_of_month.format(DB_DATE_FORMAT).to_string(),
-                end_of_month.format(DB_DATE_FORMAT).to_string()
+                end_of_month.format(DB_DATE_FORMAT).to_string(),
+                metric_type.precision()
             ],
             |row| {
-                let total: Option<u32> = row.get(0)?;
-                Ok(total.unwrap_or(0))
+                let total: Option<f64> = row.get(0)?;
+                Ok(total.unwrap_or(0.0))
             },
         )
         .map_err(|e| anyhow::anyhow!(e))
 }
+
+/// A metric value read from a weekly or monthly note.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodMetric {
+    pub name: String,
+    pub granularity: Granularity,
+    /// The period as written in note names, such as `2025-W42` or `2025-10`.
+    pub period: String,
+    pub start: String,
+    pub end: String,
+    pub value: f64,
+    pub unit: Option<String>,
+}
+
+/// Values of periods of `granularity` starting between `from` and `to`, optionally for
+/// a single metric, oldest first.
+pub fn get_period_metrics(
+    db: &DbConnection,
+    granularity: Granularity,
+    name: Option<&str>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<PeriodMetric>, anyhow::Error> {
+    let units = get_metric_units(db)?;
+    let conn = db.read()?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT name, date, value
+        FROM metrics
+        WHERE period = ?1
+        AND (?2 IS NULL OR name = ?2)
+        AND date BETWEEN ?3 AND ?4
+        ORDER BY date ASC, name ASC
+        ",
+        )
+        .with_context(|| "Failed to prepare SQL statement")?;
+    let rows = stmt.query_map(
+        params![
+            granularity.as_str(),
+            name,
+            from.format(DB_DATE_FORMAT).to_string(),
+            to.format(DB_DATE_FORMAT).to_string()
+        ],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        },
+    )?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        let (name, date, value) = row?;
+        let start = NaiveDate::parse_from_str(&date, DB_DATE_FORMAT)
+            .with_context(|| format!("Invalid period date {} in the database", date))?;
+        let period = Period { granularity, start };
+        result.push(PeriodMetric {
+            granularity,
+            period: period.label(),
+            start: date,
+            end: period.end().format(DB_DATE_FORMAT).to_string(),
+            value,
+            unit: units.get(&name).cloned(),
+            name,
+        });
+    }
+    Ok(result)
+}
+
+/// Recombines a metric's stored entries into its daily, weekly and monthly values with
+/// `aggregation`, so a new aggregation applies without reading the journal again. The
+/// entries are read back as the metric's type, so the values keep the type ingest
+/// stores them with. Returns the values that were recombined.
+pub fn reaggregate_metric(
+    db: &DbConnection,
+    name: &str,
+    aggregation: Aggregation,
+) -> Result<MetricChanges, anyhow::Error> {
+    let metric_type = get_metric_type(db, name)?;
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+
+    let mut entries: BTreeMap<(String, String), Vec<TimedValue>> = BTreeMap::new();
+    {
+        let mut stmt = tx.prepare(
+            "SELECT file_path, date, time, value FROM metric_events WHERE name = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([name], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, f64>(3)?,
+            ))
+        })?;
+        for row in rows {
+            let (file_path, date, time, value) = row?;
+            entries
+                .entry((file_path, date))
+                .or_default()
+                .push(TimedValue {
+                    value: metric_type.value_from_number(value)?,
+                    time: time
+                        .and_then(|time| NaiveTime::parse_from_str(&time, DB_TIME_FORMAT).ok()),
+                });
+        }
+    }
+
+    for ((file_path, date), values) in entries {
+        if let Some(value) = aggregation.combine(&values) {
+            tx.execute(
+                "UPDATE metrics SET value = ?1 WHERE file_path = ?2 AND name = ?3 AND date = ?4",
+                params![value, file_path, name, date],
+            )?;
+        }
+    }
+    let mut changes = MetricChanges::default();
+    add_metric_dates(&tx, name, &mut changes)?;
+    tx.commit()?;
+    Ok(changes)
+}
+
+/// One value logged for a metric, as stored in `metric_events`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoggedValue {
+    pub file_path: String,
+    pub line: Option<i64>,
+    pub time: Option<String>,
+    pub value: f64,
+}
+
+/// The values a metric was logged with on `date`, in the order they were written.
+pub fn get_logged_values(
+    db: &DbConnection,
+    name: &str,
+    date: NaiveDate,
+) -> Result<Vec<LoggedValue>, anyhow::Error> {
+    let conn = db.read()?;
+    let mut stmt = conn.prepare(
+        "SELECT file_path, line, time, value FROM metric_events
+         WHERE name = ?1 AND period = 'day' AND date = ?2
+         ORDER BY file_path, id",
+    )?;
+    let values = stmt
+        .query_map(
+            params![name, date.format(DB_DATE_FORMAT).to_string()],
+            |row| {
+                Ok(LoggedValue {
+                    file_path: row.get(0)?,
+                    line: row.get(1)?,
+                    time: row.get(2)?,
+                    value: row.get(3)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+    Ok(values)
+}
+
+/// The tracked metric values of one file's entry, and when they were last written.
+#[derive(Default)]
+struct EntryValues {
+    values: HashMap<String, f64>,
+    updated_at: Option<String>,
+}
+
+/// Stores the formula and type of a derived metric. Editing one keeps its aggregation,
+/// unit and other settings; a metric tracked from notes can't take a formula.
+pub fn save_derived_metric(
+    db: &DbConnection,
+    name: &str,
+    metric_type: MetricType,
+    formula: &str,
+) -> Result<(), anyhow::Error> {
+    let conn = db.write()?;
+    let tracked_from_notes = conn
+        .prepare("SELECT 1 FROM tracked_metrics WHERE value = ?1 AND formula IS NULL")?
+        .exists([name])?;
+    if tracked_from_notes {
+        anyhow::bail!(
+            "{} is already tracked from notes, so it can't have a formula",
+            name
+        );
+    }
+
+    conn.execute(
+        "INSERT INTO tracked_metrics (value, metric_type, formula) VALUES (?1, ?2, ?3)
+         ON CONFLICT(value) DO UPDATE SET metric_type = excluded.metric_type, formula = excluded.formula",
+        params![name, metric_type.to_string(), formula],
+    )
+    .context("Failed to set derived metric")?;
+    Ok(())
+}
+
+/// Recomputes every derived metric over the values stored in `metrics`, one entry at a
+/// time as ingestion does, for when a formula or an input's values change without the
+/// journal being read again. Formulas that fail on an entry leave a diagnostic for its
+/// file in place of the value, as they do during ingestion. Returns the derived values
+/// that were dropped or written.
+pub fn recompute_derived_metrics(
+    db: &DbConnection,
+    derived: &[DerivedMetric],
+) -> Result<MetricChanges, anyhow::Error> {
+    let mut conn = db.write()?;
+    let tx = conn.transaction()?;
+
+    let mut entries: BTreeMap<(String, String, String), EntryValues> = BTreeMap::new();
+    {
+        let mut stmt =
+            tx.prepare("SELECT file_path, date, period, name, value, updated_at FROM metrics")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, Option<String>>(5)?,
+            ))
+        })?;
+        for row in rows {
+            let (file_path, date, period, name, value, updated_at) = row?;
+            if derived.iter().any(|metric| metric.name == name) {
+                continue;
+            }
+            let entry = entries.entry((file_path, date, period)).or_default();
+            entry.values.insert(name, value);
+            entry.updated_at = entry.updated_at.take().max(updated_at);
+        }
+    }
+
+    let mut changes = MetricChanges::default();
+    for metric in derived {
+        add_metric_dates(&tx, &metric.name, &mut changes)?;
+        tx.execute("DELETE FROM metrics WHERE name = ?1", [&metric.name])?;
+        tx.execute(
+            "DELETE FROM ingest_diagnostics WHERE metric = ?1",
+            [&metric.name],
+        )?;
+    }
+    for ((file_path, date, period), mut entry) in entries {
+        for (metric, value) in derive_values(derived, &mut entry.values) {
+            match value {
+                Ok(value) => {
+                    tx.execute(
+                        "INSERT INTO metrics (file_path, name, value, date, period, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                        params![file_path, metric.name, value, date, period, entry.updated_at],
+                    )?;
+                }
+                Err(e) => add_diagnostic(
+                    &tx,
+                    &IngestDiagnostic {
+                        file_path: file_path.clone(),
+                        line: None,
+                        metric: Some(metric.name.clone()),
+                        raw: Some(metric.formula.clone()),
+                        reason: e.to_string(),
+                    },
+                )?,
+            }
+        }
+    }
+    for metric in derived {
+        add_metric_dates(&tx, &metric.name, &mut changes)?;
+    }
+    tx.commit()?;
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editing_a_formula_keeps_the_metric_settings() {
+        let db = DbConnection::open_in_memory().unwrap();
+        save_derived_metric(&db, "pages_total", MetricType::Float, "pages_read * 2").unwrap();
+        db.write()
+            .unwrap()
+            .execute(
+                "UPDATE tracked_metrics SET aggregation = 'max', unit = 'km' WHERE value = 'pages_total'",
+                [],
+            )
+            .unwrap();
+
+        save_derived_metric(&db, "pages_total", MetricType::Integer, "pages_read * 3").unwrap();
+
+        let row: (String, String, Option<String>, Option<String>) = db
+            .read()
+            .unwrap()
+            .query_row(
+                "SELECT metric_type, formula, aggregation, unit FROM tracked_metrics WHERE value = 'pages_total'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (
+                MetricType::Integer.to_string(),
+                "pages_read * 3".to_string(),
+                Some("max".to_string()),
+                Some("km".to_string())
+            )
+        );
+
+        db.write()
+            .unwrap()
+            .execute(
+                "INSERT INTO tracked_metrics (value, metric_type) VALUES ('pages_read', 'integer')",
+                [],
+            )
+            .unwrap();
+        assert!(save_derived_metric(&db, "pages_read", MetricType::Float, "1").is_err());
+    }
+}
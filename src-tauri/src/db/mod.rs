@@ -1,6 +1,9 @@
 use rusqlite::{Connection, Result};
 
+pub mod diagnostics;
+pub mod journal_roots;
 pub mod metrics;
+pub mod pool;
 pub mod seed;
 pub mod streaks;
 pub mod utils;
@@ -24,29 +27,117 @@ impl Db {
             "
             CREATE TABLE IF NOT EXISTS file_meta (
                 file_path TEXT PRIMARY KEY,
-                last_modified TEXT
+                last_modified TEXT,
+                entry_date TEXT,
+                date_source TEXT
             );
 
             CREATE TABLE IF NOT EXISTS metrics (
                 file_path TEXT NOT NULL,
                 name TEXT NOT NULL,
-                value INTEGER,
+                value REAL,
                 date TEXT NOT NULL,
                 updated_at TEXT,
                 PRIMARY KEY (file_path, name, date)
             );
 
             CREATE TABLE IF NOT EXISTS tracked_metrics (
-                value TEXT PRIMARY KEY
+                value TEXT PRIMARY KEY,
+                metric_type TEXT NOT NULL DEFAULT 'integer',
+                aliases TEXT,
+                checkbox_label TEXT
             );
 
-            CREATE TABLE IF NOT EXISTS journals_files_path (
-                value TEXT PRIMARY KEY
+            CREATE TABLE IF NOT EXISTS journal_roots (
+                path TEXT PRIMARY KEY,
+                filename_pattern TEXT,
+                enabled INTEGER NOT NULL DEFAULT 1
+            );
+
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS ingest_diagnostics (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                file_path TEXT NOT NULL,
+                line INTEGER,
+                metric TEXT,
+                raw TEXT,
+                reason TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS metric_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                file_path TEXT NOT NULL,
+                name TEXT NOT NULL,
+                date TEXT NOT NULL,
+                period TEXT NOT NULL DEFAULT 'day',
+                time TEXT,
+                value REAL NOT NULL,
+                line INTEGER
             );
 
             CREATE INDEX IF NOT EXISTS idx_file ON metrics(file_path);
+            CREATE INDEX IF NOT EXISTS idx_events_file ON metric_events(file_path);
+            CREATE INDEX IF NOT EXISTS idx_events_name ON metric_events(name, date);
+            CREATE INDEX IF NOT EXISTS idx_diagnostics_file ON ingest_diagnostics(file_path);
             ",
         )?;
+
+        // Databases created before a column existed don't pick it up from
+        // `CREATE TABLE IF NOT EXISTS`, so add it in place.
+        self.add_column_if_missing(
+            "tracked_metrics",
+            "metric_type",
+            "TEXT NOT NULL DEFAULT 'integer'",
+        )?;
+        self.add_column_if_missing("tracked_metrics", "aliases", "TEXT")?;
+        self.add_column_if_missing("tracked_metrics", "checkbox_label", "TEXT")?;
+        self.add_column_if_missing("tracked_metrics", "aggregation", "TEXT")?;
+        self.add_column_if_missing("tracked_metrics", "formula", "TEXT")?;
+        self.add_column_if_missing("tracked_metrics", "unit", "TEXT")?;
+        self.add_column_if_missing("file_meta", "entry_date", "TEXT")?;
+        self.add_column_if_missing("file_meta", "date_source", "TEXT")?;
+        self.add_column_if_missing("file_meta", "modified_ns", "INTEGER")?;
+        self.add_column_if_missing("file_meta", "size", "INTEGER")?;
+        self.add_column_if_missing("file_meta", "content_hash", "TEXT")?;
+        self.add_column_if_missing("file_meta", "period", "TEXT")?;
+        self.add_column_if_missing("file_meta", "stored_ns", "INTEGER")?;
+        self.add_column_if_missing("metrics", "period", "TEXT NOT NULL DEFAULT 'day'")?;
+        self.migrate_journals_files_path()?;
+        Ok(())
+    }
+
+    /// Moves the single journal folder of older databases into `journal_roots`.
+    fn migrate_journals_files_path(&self) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'journals_files_path'",
+        )?;
+        if stmt.exists([])? {
+            self.conn.execute_batch(
+                "
+                INSERT OR IGNORE INTO journal_roots (path) SELECT value FROM journals_files_path;
+                DROP TABLE journals_files_path;
+                ",
+            )?;
+        }
+        Ok(())
+    }
+
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> Result<()> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+            table
+        ))?;
+        if !stmt.exists([column])? {
+            self.conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                [],
+            )?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,147 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+
+use crate::db::Db;
+
+/// Read connections kept open for commands and the sync workers' lookups.
+const READ_POOL_SIZE: u32 = 4;
+
+/// How long a statement waits on a locked database before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type ReadConnection = PooledConnection<SqliteConnectionManager>;
+pub type WriteConnection<'a> = MutexGuard<'a, Connection>;
+
+/// The app's database: a pool of read-only connections and the one connection every
+/// write goes through. In WAL mode readers see the last committed state without
+/// waiting, so the dashboard stays responsive while a resync writes.
+pub struct DbPool {
+    readers: Pool<SqliteConnectionManager>,
+    writer: Mutex<Connection>,
+}
+
+impl DbPool {
+    /// Opens the database at `path`, creating and migrating it as needed.
+    pub fn open(path: &str) -> Result<Self, anyhow::Error> {
+        let db = Db::new(path)?;
+        db.conn
+            .pragma_update(None, "journal_mode", "WAL")
+            .with_context(|| "Failed to turn on WAL mode")?;
+        Self::with_writer(db, SqliteConnectionManager::file(path))
+    }
+
+    /// Opens a fresh in-memory database shared by all of the pool's connections. It has
+    /// no WAL, so a read fails while a write transaction is open; tests that read
+    /// during a write use [`DbPool::open`] on a temporary file instead.
+    pub fn open_in_memory() -> Result<Self, anyhow::Error> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let uri = format!(
+            "file:cadence-{}-{}?mode=memory&cache=shared",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let db = Db::new(&uri)?;
+        Self::with_writer(db, SqliteConnectionManager::file(uri))
+    }
+
+    fn with_writer(db: Db, readers: SqliteConnectionManager) -> Result<Self, anyhow::Error> {
+        db.init_db()
+            .with_context(|| "Failed to create or migrate the database")?;
+        db.conn.busy_timeout(BUSY_TIMEOUT)?;
+
+        let readers = readers.with_init(|conn| {
+            conn.busy_timeout(BUSY_TIMEOUT)?;
+            conn.pragma_update(None, "query_only", true)
+        });
+        let readers = Pool::builder()
+            .max_size(READ_POOL_SIZE)
+            .build(readers)
+            .with_context(|| "Failed to open read connections")?;
+
+        Ok(DbPool {
+            readers,
+            writer: Mutex::new(db.into_connection()),
+        })
+    }
+
+    /// A connection for queries. It refuses to write.
+    pub fn read(&self) -> Result<ReadConnection, anyhow::Error> {
+        self.readers
+            .get()
+            .with_context(|| "Failed to get a read connection")
+    }
+
+    /// The write connection, once no one else is writing.
+    pub fn write(&self) -> Result<WriteConnection<'_>, anyhow::Error> {
+        // A writer that panicked mid-transaction had it rolled back when the
+        // transaction dropped, so the connection is still good to use.
+        Ok(self.writer.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::OptionalExtension;
+
+    #[test]
+    fn test_reads_see_committed_writes_but_cannot_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cadence.db");
+        let db = DbPool::open(path.to_str().unwrap()).unwrap();
+
+        let journal_mode: String = db
+            .read()
+            .unwrap()
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        db.write()
+            .unwrap()
+            .execute("INSERT INTO settings (key, value) VALUES ('a', 'b')", [])
+            .unwrap();
+        let reader = db.read().unwrap();
+        let value: String = reader
+            .query_row("SELECT value FROM settings WHERE key = 'a'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(value, "b");
+        assert!(reader.execute("DELETE FROM settings", []).is_err());
+    }
+
+    #[test]
+    fn test_reads_during_a_write_see_the_last_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cadence.db");
+        let db = DbPool::open(path.to_str().unwrap()).unwrap();
+        let stored = || -> Option<String> {
+            db.read()
+                .unwrap()
+                .query_row("SELECT value FROM settings WHERE key = 'a'", [], |row| {
+                    row.get(0)
+                })
+                .optional()
+                .unwrap()
+        };
+
+        let mut writer = db.write().unwrap();
+        let tx = writer.transaction().unwrap();
+        tx.execute("INSERT INTO settings (key, value) VALUES ('a', 'b')", [])
+            .unwrap();
+        assert_eq!(stored(), None);
+        tx.commit().unwrap();
+        assert_eq!(stored(), Some("b".to_string()));
+    }
+}
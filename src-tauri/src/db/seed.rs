@@ -2,7 +2,13 @@ use chrono::{Days, Local};
 use rand::{rng, Rng};
 use rusqlite::params;
 
-use crate::{core::read_journal::{Metric, DB_DATE_FORMAT, DB_DATE_TIME_FORMAT}, DbConnection};
+use crate::{
+    core::{
+        metric_value::MetricValue,
+        read_journal::{Metric, DB_DATE_FORMAT, DB_DATE_TIME_FORMAT},
+    },
+    DbConnection,
+};
 
 pub fn seed_development_data(db: &DbConnection) -> Result<(), anyhow::Error> {
     if is_db_populated(db) {
@@ -32,7 +38,9 @@ pub fn seed_development_data(db: &DbConnection) -> Result<(), anyhow::Error> {
 }
 
 fn is_db_populated(db: &DbConnection) -> bool {
-    let conn = db.lock().unwrap();
+    let Ok(conn) = db.read() else {
+        return false;
+    };
     let count: i64 = conn
         .query_row("SELECT COUNT(*) FROM metrics", [], |row| row.get(0))
         .unwrap_or(0);
@@ -60,7 +68,7 @@ fn seed_consistent_habit(
             let metric = Metric {
                 file_path: format!("journal/{}.md", date.format("%Y-%m-%d")),
                 name: name.to_string(),
-                value,
+                value: MetricValue::Integer(value),
                 date,
             };
             insert_metric(db, &metric)?;
@@ -89,7 +97,7 @@ fn seed_sporadic_habit(
             let metric = Metric {
                 file_path: format!("journal/{}.md", date.format("%Y-%m-%d")),
                 name: name.to_string(),
-                value,
+                value: MetricValue::Integer(value),
                 date,
             };
             insert_metric(db, &metric)?;
@@ -119,7 +127,7 @@ fn seed_learning_habit(
             let metric = Metric {
                 file_path: format!("journal/{}.md", date.format("%Y-%m-%d")),
                 name: name.to_string(),
-                value,
+                value: MetricValue::Integer(value),
                 date,
             };
             insert_metric(db, &metric)?;
@@ -149,7 +157,7 @@ fn seed_reading_habit(
             let metric = Metric {
                 file_path: format!("journal/{}.md", date.format("%Y-%m-%d")),
                 name: name.to_string(),
-                value,
+                value: MetricValue::Integer(value),
                 date,
             };
             insert_metric(db, &metric)?;
@@ -181,7 +189,7 @@ fn seed_habit_with_breaks(
             let metric = Metric {
                 file_path: format!("journal/{}.md", date.format("%Y-%m-%d")),
                 name: name.to_string(),
-                value,
+                value: MetricValue::Integer(value),
                 date,
             };
             insert_metric(db, &metric)?;
@@ -211,7 +219,7 @@ fn seed_recent_habit(
             let metric = Metric {
                 file_path: format!("journal/{}.md", date.format("%Y-%m-%d")),
                 name: name.to_string(),
-                value,
+                value: MetricValue::Integer(value),
                 date,
             };
             insert_metric(db, &metric)?;
@@ -221,7 +229,7 @@ fn seed_recent_habit(
 }
 
 pub fn insert_metric(db: &DbConnection, metric: &Metric) -> Result<(), anyhow::Error> {
-    let conn = db.lock().unwrap();
+    let conn = db.write()?;
     conn.execute(
         "INSERT OR REPLACE INTO metrics(file_path, name, value, date,updated_at) VALUES (?1, ?2, ?3, ?4,?5)",
         params![
@@ -245,7 +253,7 @@ fn seed_tracked_metrics(db: &DbConnection) -> Result<(), anyhow::Error> {
         "water_glasses"
     ];
     
-    let conn = db.lock().unwrap();
+    let conn = db.write()?;
     for metric in metrics {
         conn.execute(
             "INSERT OR IGNORE INTO tracked_metrics (value) VALUES (?1)",
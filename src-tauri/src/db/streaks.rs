@@ -6,14 +6,15 @@ use rusqlite::params;
 use crate::{core::read_journal::DB_DATE_FORMAT, DbConnection};
 
 pub fn get_habit_streak(db: &DbConnection, name: &str) -> Result<i64, anyhow::Error> {
-    let conn = db.lock().unwrap();
+    let conn = db.read()?;
     let today = Local::now().date_naive();
     let mut current_date = today
         .checked_sub_days(chrono::Days::new(1))
         .ok_or_else(|| anyhow::anyhow!("Failed to compute yesterday"))?; // Start from yesterday
 
     // Query all relevant dates where this habit has value > 0
-    let mut stmt = conn.prepare("SELECT date FROM metrics WHERE name = ?1 AND value > 0")?;
+    let mut stmt =
+        conn.prepare("SELECT date FROM metrics WHERE name = ?1 AND value > 0 AND period = 'day'")?;
     let date_iter = stmt.query_map(params![name], |row| row.get::<_, String>(0))?;
 
     let mut logged_dates = HashSet::new();
@@ -49,11 +50,13 @@ pub fn get_habit_streak(db: &DbConnection, name: &str) -> Result<i64, anyhow::Er
 }
 
 pub fn get_longest_habit_streak(db: &DbConnection, name: &str) -> Result<i64, anyhow::Error> {
-    let conn = db.lock().unwrap();
+    let conn = db.read()?;
 
     // Query all relevant dates where this habit has value > 0
     let mut stmt =
-        conn.prepare("SELECT date FROM metrics WHERE name = ?1 AND value > 0 ORDER BY date ASC")?;
+        conn.prepare(
+        "SELECT date FROM metrics WHERE name = ?1 AND value > 0 AND period = 'day' ORDER BY date ASC",
+    )?;
     let date_iter = stmt.query_map(params![name], |row| row.get::<_, String>(0))?;
 
     compute_longest_streak(date_iter)
@@ -102,10 +105,12 @@ mod tests {
     use anyhow::Context;
     use chrono::{Days, Local};
     use rand::{rng, Rng};
-    use rusqlite::Connection;
 
     use crate::{
-        core::read_journal::{Metric, DB_DATE_FORMAT},
+        core::{
+            metric_value::MetricValue,
+            read_journal::{Metric, DB_DATE_FORMAT},
+        },
         db::seed::insert_metric,
     };
 
@@ -115,27 +120,8 @@ mod tests {
     const METRIC_NAME: &str = "dsa_solved";
 
     fn setup_test_db() -> Result<DbConnection, anyhow::Error> {
-        let conn = Connection::open_in_memory()
-            .with_context(|| "Failed to open in-memory database with error".to_string())?;
-
-        let db = DbConnection::new(conn);
-
-        // Initialize the database schema
-        {
-            let conn = db.lock().unwrap();
-            conn.execute(
-                "CREATE TABLE IF NOT EXISTS metrics (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    file_path TEXT NOT NULL,
-                    name TEXT NOT NULL,
-                    value INTEGER NOT NULL,
-                    date TEXT NOT NULL
-                )",
-                [],
-            )?;
-        }
-
-        Ok(db)
+        DbConnection::open_in_memory()
+            .with_context(|| "Failed to open in-memory database with error".to_string())
     }
 
     fn seed_test_data(
@@ -145,7 +131,7 @@ mod tests {
     ) -> Result<(), anyhow::Error> {
         let current_date = Local::now().date_naive();
         for i in 0..=days {
-            let value: u32 = rng().random_range(1..=10);
+            let value: i64 = rng().random_range(1..=10);
             let days_back = i as u64;
             let date = current_date
                 .checked_sub_days(Days::new(days_back))
@@ -153,7 +139,7 @@ mod tests {
             let metric = Metric {
                 file_path: format!("test_file_{}.md", date.format("%Y-%m-%d")),
                 name: metric_name.to_string(),
-                value,
+                value: MetricValue::Integer(value),
                 date,
             };
             insert_metric(db, &metric)?;
@@ -177,7 +163,7 @@ mod tests {
         let db = setup_test_db().expect("Failed to setup test database");
         seed_database(&db).expect("Failed to seed database");
 
-        let conn = db.lock().unwrap();
+        let conn = db.write().unwrap();
         let two_days_ago = Local::now()
             .date_naive()
             .checked_sub_days(Days::new(2))
@@ -201,7 +187,7 @@ mod tests {
         let db = setup_test_db().expect("Failed to setup test database");
         seed_database(&db).expect("Failed to seed database");
 
-        let conn = db.lock().unwrap();
+        let conn = db.write().unwrap();
         let yesterday = Local::now()
             .date_naive()
             .checked_sub_days(Days::new(1))
@@ -234,7 +220,7 @@ mod tests {
         let db = setup_test_db().expect("Failed to setup test database");
 
         // Create a pattern: 3 days, gap, 5 days, gap, 2 days
-        let conn_guard = db.lock().unwrap();
+        let conn_guard = db.write().unwrap();
         let current_date = Local::now().date_naive();
 
         // First streak: 3 days (days 0, 1, 2)
@@ -305,7 +291,7 @@ mod tests {
     fn test_get_longest_habit_streak_single_entry() {
         let db = setup_test_db().expect("Failed to setup test database");
 
-        let conn_guard = db.lock().unwrap();
+        let conn_guard = db.write().unwrap();
         let current_date = Local::now().date_naive();
 
         conn_guard
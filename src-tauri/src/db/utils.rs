@@ -1,8 +1,45 @@
-use crate::DbConnection;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+    time::{Duration, SystemTime},
+};
 
-pub fn get_all_habits(db: &DbConnection) -> Result<Vec<String>, rusqlite::Error> {
-    let conn = db.lock().unwrap();
-    let mut stmt = conn.prepare("SELECT DISTINCT m.name FROM metrics m LEFT JOIN tracked_metrics tm ON m.name = tm.value WHERE tm.value IS NOT NULL")?;
+use chrono::{DateTime, Local, NaiveDate};
+use rusqlite::params;
+
+use crate::{
+    core::{
+        change_events::{MetricChanges, RemovedFiles},
+        filename_pattern::FilenamePattern,
+        inline_fields::FieldPrecedence,
+        metric_value::MetricType,
+        read_journal::{unix_ns, DB_DATE_FORMAT, DB_DATE_TIME_FORMAT},
+        source_format::SourceFormats,
+    },
+    DbConnection,
+};
+
+pub const FILENAME_PATTERN_SETTING: &str = "filename_pattern";
+pub const DATE_FIELD_SETTING: &str = "date_field";
+pub const PREFER_DATE_FIELD_SETTING: &str = "prefer_date_field";
+pub const INCLUDE_GLOBS_SETTING: &str = "include_globs";
+pub const EXCLUDE_GLOBS_SETTING: &str = "exclude_globs";
+pub const INLINE_FIELDS_SETTING: &str = "inline_fields";
+pub const FIELD_PRECEDENCE_SETTING: &str = "field_precedence";
+pub const NORMALIZE_METRIC_KEYS_SETTING: &str = "normalize_metric_keys";
+pub const SOURCE_FORMATS_SETTING: &str = "source_formats";
+pub const WATCH_DEBOUNCE_SETTING: &str = "watch_debounce_ms";
+pub const RECONCILE_INTERVAL_SETTING: &str = "reconcile_interval_minutes";
+
+/// How long the watcher waits for edits to settle when no quiet period is set.
+pub const DEFAULT_WATCH_DEBOUNCE: Duration = Duration::from_millis(1000);
+
+/// How often the journal is checked for changes the watcher missed, unless set.
+pub const DEFAULT_RECONCILE_INTERVAL_MINUTES: u64 = 30;
+
+pub fn get_all_habits(db: &DbConnection) -> Result<Vec<String>, anyhow::Error> {
+    let conn = db.read()?;
+    let mut stmt = conn.prepare("SELECT DISTINCT m.name FROM metrics m LEFT JOIN tracked_metrics tm ON m.name = tm.value WHERE tm.value IS NOT NULL AND m.period = 'day'")?;
     let habit_iter = stmt.query_map([], |row| row.get(0))?;
 
     let mut habits = Vec::new();
@@ -13,16 +50,384 @@ pub fn get_all_habits(db: &DbConnection) -> Result<Vec<String>, rusqlite::Error>
     Ok(habits)
 }
 
-pub fn get_journal_files_path(db: &DbConnection) -> Result<Option<String>, anyhow::Error> {
-    let conn = db
-        .lock()
-        .map_err(|e| anyhow::anyhow!("Failed to lock connection: {}", e))?;
+pub fn get_metric_type(db: &DbConnection, name: &str) -> Result<MetricType, anyhow::Error> {
+    let conn = db.read()?;
+
+    let mut stmt = conn.prepare("SELECT metric_type FROM tracked_metrics WHERE value = ?1")?;
+
+    match stmt.query_row([name], |row| row.get::<_, String>(0)) {
+        Ok(metric_type) => metric_type.parse(),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(MetricType::default()),
+        Err(e) => Err(anyhow::anyhow!(
+            "Failed to get metric type of {}: {}",
+            name,
+            e
+        )),
+    }
+}
+
+/// The unit a metric's values are stored in: the one set on it, or the one its type
+/// implies, such as minutes for durations.
+pub fn get_metric_unit(db: &DbConnection, name: &str) -> Result<Option<String>, anyhow::Error> {
+    Ok(get_metric_units(db)?.remove(name))
+}
+
+/// The units of every tracked metric that has one, by metric name.
+pub fn get_metric_units(db: &DbConnection) -> Result<HashMap<String, String>, anyhow::Error> {
+    let conn = db.read()?;
+
+    let mut stmt = conn.prepare("SELECT value, metric_type, unit FROM tracked_metrics")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
+    })?;
+
+    let mut units = HashMap::new();
+    for row in rows {
+        let (name, metric_type, unit) = row?;
+        let implied = metric_type
+            .parse::<MetricType>()
+            .ok()
+            .and_then(|metric_type| metric_type.implied_unit())
+            .map(str::to_string);
+        if let Some(unit) = unit.or(implied) {
+            units.insert(name, unit);
+        }
+    }
+    Ok(units)
+}
+
+pub fn get_setting(db: &DbConnection, key: &str) -> Result<Option<String>, anyhow::Error> {
+    let conn = db.read()?;
 
-    let mut stmt = conn.prepare("SELECT value FROM journals_files_path")?;
+    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
 
-    match stmt.query_row([], |row| row.get(0)) {
-        Ok(path) => Ok(Some(path)),
+    match stmt.query_row([key], |row| row.get(0)) {
+        Ok(value) => Ok(Some(value)),
         Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(anyhow::anyhow!("Failed to get journal files path: {}", e)),
+        Err(e) => Err(anyhow::anyhow!("Failed to get setting {}: {}", key, e)),
+    }
+}
+
+pub fn set_setting(db: &DbConnection, key: &str, value: &str) -> Result<(), anyhow::Error> {
+    let conn = db.write()?;
+
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+pub fn get_filename_pattern(db: &DbConnection) -> Result<FilenamePattern, anyhow::Error> {
+    match get_setting(db, FILENAME_PATTERN_SETTING)? {
+        Some(pattern) => pattern.parse(),
+        None => Ok(FilenamePattern::default()),
+    }
+}
+
+/// The front matter key entries fall back to (or prefer) for their date, if any.
+pub fn get_date_field(db: &DbConnection) -> Result<Option<String>, anyhow::Error> {
+    Ok(get_setting(db, DATE_FIELD_SETTING)?.filter(|field| !field.trim().is_empty()))
+}
+
+pub fn get_prefer_date_field(db: &DbConnection) -> Result<bool, anyhow::Error> {
+    Ok(get_setting(db, PREFER_DATE_FIELD_SETTING)?.is_some_and(|value| value == "true"))
+}
+
+pub fn get_inline_fields(db: &DbConnection) -> Result<bool, anyhow::Error> {
+    Ok(get_setting(db, INLINE_FIELDS_SETTING)?.is_some_and(|value| value == "true"))
+}
+
+pub fn get_field_precedence(db: &DbConnection) -> Result<FieldPrecedence, anyhow::Error> {
+    match get_setting(db, FIELD_PRECEDENCE_SETTING)? {
+        Some(precedence) => precedence.parse(),
+        None => Ok(FieldPrecedence::default()),
+    }
+}
+
+pub fn get_normalize_metric_keys(db: &DbConnection) -> Result<bool, anyhow::Error> {
+    Ok(get_setting(db, NORMALIZE_METRIC_KEYS_SETTING)?.is_some_and(|value| value == "true"))
+}
+
+/// The journal formats files are read in, Markdown alone by default.
+pub fn get_source_formats(db: &DbConnection) -> Result<SourceFormats, anyhow::Error> {
+    SourceFormats::from_names(&get_list_setting(db, SOURCE_FORMATS_SETTING)?)
+}
+
+/// How long a changed file has to go without further changes before it is read.
+pub fn get_watch_debounce(db: &DbConnection) -> Result<Duration, anyhow::Error> {
+    match get_setting(db, WATCH_DEBOUNCE_SETTING)? {
+        Some(millis) => millis
+            .parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|e| anyhow::anyhow!("Invalid watch debounce {}: {}", millis, e)),
+        None => Ok(DEFAULT_WATCH_DEBOUNCE),
     }
 }
+
+/// How often the journal is checked for changes the watcher missed, or `None` when the
+/// periodic check is turned off.
+pub fn get_reconcile_interval(db: &DbConnection) -> Result<Option<Duration>, anyhow::Error> {
+    let minutes = match get_setting(db, RECONCILE_INTERVAL_SETTING)? {
+        Some(minutes) => minutes
+            .parse::<u64>()
+            .map_err(|e| anyhow::anyhow!("Invalid reconcile interval {}: {}", minutes, e))?,
+        None => DEFAULT_RECONCILE_INTERVAL_MINUTES,
+    };
+    Ok((minutes > 0).then(|| Duration::from_secs(minutes * 60)))
+}
+
+/// Forgets which files have been read, so the next resync parses every file again.
+pub fn clear_file_meta(db: &DbConnection) -> Result<(), anyhow::Error> {
+    let conn = db.write()?;
+
+    conn.execute("DELETE FROM file_meta", [])?;
+    Ok(())
+}
+
+/// Paths of every file with stored metrics, metadata or diagnostics.
+pub fn stored_file_paths(conn: &rusqlite::Connection) -> Result<Vec<String>, anyhow::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT file_path FROM file_meta
+         UNION SELECT file_path FROM metrics
+         UNION SELECT file_path FROM ingest_diagnostics",
+    )?;
+    let file_paths = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+    Ok(file_paths)
+}
+
+/// Paths of every file with stored rows at or under `path`. Files under it are those
+/// sorting between `path/` and the character after the separator, so the `file_path`
+/// indexes find them without every stored path being listed.
+fn stored_file_paths_under(
+    conn: &rusqlite::Connection,
+    path: &Path,
+) -> Result<Vec<String>, anyhow::Error> {
+    let path = path.to_string_lossy();
+    let folder = path.trim_end_matches(MAIN_SEPARATOR);
+    let after_folder = char::from(MAIN_SEPARATOR as u8 + 1);
+    let mut stmt = conn.prepare(
+        "SELECT file_path FROM file_meta WHERE file_path = ?1 OR (file_path >= ?2 AND file_path < ?3)
+         UNION SELECT file_path FROM metrics WHERE file_path = ?1 OR (file_path >= ?2 AND file_path < ?3)
+         UNION SELECT file_path FROM ingest_diagnostics WHERE file_path = ?1 OR (file_path >= ?2 AND file_path < ?3)",
+    )?;
+    let file_paths = stmt
+        .query_map(
+            params![
+                path,
+                format!("{}{}", folder, MAIN_SEPARATOR),
+                format!("{}{}", folder, after_folder)
+            ],
+            |row| row.get::<_, String>(0),
+        )?
+        .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+    Ok(file_paths)
+}
+
+/// Adds the metrics stored for a file, and the dates they are stored under, to
+/// `changes`.
+pub fn add_stored_metrics(
+    conn: &rusqlite::Connection,
+    file_path: &str,
+    changes: &mut MetricChanges,
+) -> Result<(), anyhow::Error> {
+    let mut stmt = conn.prepare_cached("SELECT name, date FROM metrics WHERE file_path = ?1")?;
+    let rows = stmt.query_map([file_path], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for row in rows {
+        let (name, date) = row?;
+        if let Ok(date) = NaiveDate::parse_from_str(&date, DB_DATE_FORMAT) {
+            changes.add(&name, date);
+        }
+    }
+    Ok(())
+}
+
+/// Adds a metric, and the span of dates its values are stored under, to `changes`.
+pub fn add_metric_dates(
+    conn: &rusqlite::Connection,
+    name: &str,
+    changes: &mut MetricChanges,
+) -> Result<(), anyhow::Error> {
+    let (first, last): (Option<String>, Option<String>) = conn.query_row(
+        "SELECT MIN(date), MAX(date) FROM metrics WHERE name = ?1",
+        [name],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    for date in [first, last].into_iter().flatten() {
+        if let Ok(date) = NaiveDate::parse_from_str(&date, DB_DATE_FORMAT) {
+            changes.add(name, date);
+        }
+    }
+    Ok(())
+}
+
+/// Drops the metrics and metadata of the file at `path`, or of every file under it
+/// when it is a folder. Used for deleted files and for removed or disabled roots.
+pub fn delete_file_rows(db: &DbConnection, path: &Path) -> Result<RemovedFiles, anyhow::Error> {
+    let mut conn = db.write()?;
+
+    let file_paths = stored_file_paths_under(&conn, path)?;
+    delete_rows_of(&mut conn, file_paths)
+}
+
+/// Drops every value stored for a metric, both the daily values and the entries they
+/// were combined from.
+pub fn delete_metric_rows(conn: &rusqlite::Connection, name: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM metrics WHERE name = ?1", [name])?;
+    conn.execute("DELETE FROM metric_events WHERE name = ?1", [name])?;
+    Ok(())
+}
+
+/// Drops the stored rows of every file for which `remove` returns true, such as the
+/// files of a journal format that was turned off, and returns what they held. Every
+/// stored path is checked, so files under one path go through [`delete_file_rows`].
+pub fn delete_file_rows_where(
+    db: &DbConnection,
+    remove: impl Fn(&Path) -> bool,
+) -> Result<RemovedFiles, anyhow::Error> {
+    let mut conn = db.write()?;
+
+    let file_paths = stored_file_paths(&conn)?
+        .into_iter()
+        .filter(|file_path| remove(Path::new(file_path)))
+        .collect::<Vec<_>>();
+    delete_rows_of(&mut conn, file_paths)
+}
+
+/// Drops the stored rows of `file_paths`, except those of files stored since `since`.
+/// Lets a caller that listed the files earlier leave alone any that a sync has read
+/// in the meantime.
+pub fn delete_file_rows_stored_before(
+    db: &DbConnection,
+    file_paths: Vec<String>,
+    since: SystemTime,
+) -> Result<RemovedFiles, anyhow::Error> {
+    let since_ns = unix_ns(since);
+    let since = DateTime::<Local>::from(since)
+        .format(DB_DATE_TIME_FORMAT)
+        .to_string();
+    let mut conn = db.write()?;
+
+    let mut stale = Vec::with_capacity(file_paths.len());
+    {
+        // Files that failed to parse have no `file_meta` row, only diagnostics, which
+        // are timed to the second; one stored in the same second counts as newer.
+        let mut stmt = conn.prepare(
+            "SELECT (SELECT stored_ns FROM file_meta WHERE file_path = ?1),
+                    (SELECT MAX(created_at) FROM ingest_diagnostics WHERE file_path = ?1)",
+        )?;
+        for file_path in file_paths {
+            let (stored_ns, diagnosed_at) = stmt.query_row([&file_path], |row| {
+                Ok((
+                    row.get::<_, Option<i64>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                ))
+            })?;
+            let stored_since = match stored_ns {
+                Some(stored_ns) => stored_ns >= since_ns,
+                None => diagnosed_at.is_some_and(|diagnosed_at| diagnosed_at >= since),
+            };
+            if !stored_since {
+                stale.push(file_path);
+            }
+        }
+    }
+    delete_rows_of(&mut conn, stale)
+}
+
+fn delete_rows_of(
+    conn: &mut rusqlite::Connection,
+    file_paths: Vec<String>,
+) -> Result<RemovedFiles, anyhow::Error> {
+    let tx = conn.transaction()?;
+    let mut changes = MetricChanges::default();
+    for file_path in &file_paths {
+        add_stored_metrics(&tx, file_path, &mut changes)?;
+        tx.execute("DELETE FROM metrics WHERE file_path = ?1", [file_path])?;
+        tx.execute(
+            "DELETE FROM metric_events WHERE file_path = ?1",
+            [file_path],
+        )?;
+        tx.execute("DELETE FROM file_meta WHERE file_path = ?1", [file_path])?;
+        tx.execute(
+            "DELETE FROM ingest_diagnostics WHERE file_path = ?1",
+            [file_path],
+        )?;
+    }
+    tx.commit()?;
+    Ok(RemovedFiles {
+        paths: file_paths,
+        changes,
+    })
+}
+
+/// Moves the stored rows of a renamed file, or of every file under a renamed folder,
+/// over to the new path. Returns the paths the rows were moved from.
+pub fn rename_file_rows(
+    db: &DbConnection,
+    from: &Path,
+    to: &Path,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut conn = db.write()?;
+
+    let file_paths = stored_file_paths_under(&conn, from)?;
+    let tx = conn.transaction()?;
+    for file_path in &file_paths {
+        let renamed: PathBuf = match Path::new(file_path).strip_prefix(from) {
+            Ok(relative) if relative.as_os_str().is_empty() => to.to_path_buf(),
+            Ok(relative) => to.join(relative),
+            Err(_) => continue,
+        };
+        let renamed = renamed.to_string_lossy().to_string();
+
+        // Rows already stored under the new path are stale once the old file lands there.
+        tx.execute("DELETE FROM metrics WHERE file_path = ?1", [&renamed])?;
+        tx.execute("DELETE FROM metric_events WHERE file_path = ?1", [&renamed])?;
+        tx.execute("DELETE FROM file_meta WHERE file_path = ?1", [&renamed])?;
+        tx.execute(
+            "UPDATE metrics SET file_path = ?1 WHERE file_path = ?2",
+            params![renamed, file_path],
+        )?;
+        tx.execute(
+            "UPDATE metric_events SET file_path = ?1 WHERE file_path = ?2",
+            params![renamed, file_path],
+        )?;
+        // Its date may come from the new name, so the stamp is reset to have it re-read.
+        tx.execute(
+            "UPDATE file_meta SET file_path = ?1, modified_ns = NULL, size = NULL, content_hash = NULL WHERE file_path = ?2",
+            params![renamed, file_path],
+        )?;
+        // The renamed file is read again, which reports its problems afresh.
+        tx.execute(
+            "DELETE FROM ingest_diagnostics WHERE file_path IN (?1, ?2)",
+            params![renamed, file_path],
+        )?;
+    }
+    tx.commit()?;
+    Ok(file_paths)
+}
+
+/// Reads a list setting, stored as a JSON array of strings.
+pub fn get_list_setting(db: &DbConnection, key: &str) -> Result<Vec<String>, anyhow::Error> {
+    match get_setting(db, key)? {
+        Some(value) => serde_json::from_str(&value)
+            .map_err(|e| anyhow::anyhow!("Failed to parse setting {}: {}", key, e)),
+        None => Ok(Vec::new()),
+    }
+}
+
+pub fn set_list_setting(
+    db: &DbConnection,
+    key: &str,
+    values: &[String],
+) -> Result<(), anyhow::Error> {
+    set_setting(db, key, &serde_json::to_string(values)?)
+}
@@ -1,7 +1,6 @@
 use std::env;
 use std::sync::{mpsc, Mutex};
 
-use rusqlite::Connection;
 use tauri::Manager;
 
 mod commands;
@@ -13,18 +12,33 @@ use commands::dashboard::*;
 use commands::settings::*;
 use commands::streaks::*;
 use dotenvy::dotenv;
-use tauri_plugin_notification::NotificationExt;
 
+use crate::commands::diagnostics::get_ingest_diagnostics;
 use crate::commands::recent_activity::get_recent_activity;
+use crate::commands::sync::get_sync_status;
 use crate::core::file_watcher::WatchCommand;
+use crate::core::ingest_cache::IngestConfigCache;
+use crate::core::reconcile::start_reconcile_schedule;
+use crate::core::sync_queue::SyncQueue;
 use crate::core::sync_worker::setup_sync_worker;
-use crate::db::utils::get_journal_files_path;
-use crate::db::Db;
+use crate::db::journal_roots::get_enabled_journal_roots;
+use crate::db::pool::DbPool;
 
 // Type aliases to prevent runtime panics
-pub type DbConnection = Mutex<Connection>;
+pub type DbConnection = DbPool;
 pub type WatcherState = Mutex<Option<mpsc::Sender<WatchCommand>>>;
 
+/// What the benchmarks in `benches/` drive. Not part of the app's interface.
+#[doc(hidden)]
+pub mod bench_support {
+    pub use crate::commands::dashboard::get_habit_metrics;
+    pub use crate::core::metric_value::{Aggregation, MetricDefinition, MetricType};
+    pub use crate::core::read_journal::{parse_journal_file, write_file_updates};
+    pub use crate::core::sync_worker::MAX_BATCH_SIZE;
+    pub use crate::core::IngestConfig;
+    pub use crate::db::pool::DbPool;
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     if let Err(e) = dotenv() {
@@ -48,39 +62,41 @@ pub fn run() {
             let db_path = db_path.unwrap().join("cadance.db");
             let db_path = db_path.to_str().unwrap();
 
-            let db = Db::new(db_path)?;
-            if let Err(err) = db.init_db() {
-                eprintln!("Failed to initialize the database: {}", err);
-            }
+            let db = DbConnection::open(db_path)?;
 
             let watcher: WatcherState = Mutex::new(None);
-            let tx = setup_sync_worker(app.handle().clone());
 
-            app.manage(DbConnection::new(db.into_connection()));
+            app.manage(db);
             app.manage(watcher);
-            app.manage(tx);
+            app.manage(SyncQueue::default());
+            app.manage(IngestConfigCache::default());
+            setup_sync_worker(app.handle().clone());
+            start_reconcile_schedule(app.handle().clone());
 
             tauri::async_runtime::spawn({
                 let app_handle = app.handle().clone();
 
                 async move {
-                    // Get journal path from settings
+                    // Get journal roots from settings
                     let db_state = app_handle.state::<DbConnection>();
-                    let journal_path = match get_journal_files_path(&db_state) {
-                        Ok(path) => path,
+                    let journal_roots = match get_enabled_journal_roots(&db_state) {
+                        Ok(roots) => roots,
                         Err(e) => {
-                            eprintln!("Error getting journal path from settings: {}", e);
-                            None
+                            eprintln!("Error getting journal roots from settings: {}", e);
+                            Vec::new()
                         }
                     };
 
-                    // Initialize core with optional journal path
+                    // Initialize core with the enabled journal roots
                     tauri::async_runtime::spawn_blocking({
                         let app_handle_clone = app_handle.clone();
-                        move || match core::init(app_handle_clone.clone(), journal_path) {
+                        move || match core::init(app_handle_clone.clone(), journal_roots) {
                             Ok(handle_opt) => {
                                 let watcher_state = app_handle_clone.state::<WatcherState>();
-                                watcher_state.lock().unwrap().replace(handle_opt);
+                                watcher_state
+                                    .lock()
+                                    .unwrap_or_else(|e| e.into_inner())
+                                    .replace(handle_opt);
                             }
                             Err(e) => eprintln!("Error during core initialization: {}", e),
                         }
@@ -107,6 +123,7 @@ pub fn run() {
             get_longest_streak,
             get_dashboard_metrics,
             get_weekly_metric_stats,
+            get_metric_events,
             //streak grid
             get_current_streak_data,
             // analytics
@@ -114,13 +131,33 @@ pub fn run() {
             // new analytic ones
             get_analytics_summary,
             get_weekly_activity,
+            get_periodic_metrics,
+            get_ingest_diagnostics,
+            get_sync_status,
             //settings
             get_settings,
             is_journal_path_configured,
             set_journal_files_path,
+            list_journal_roots,
+            add_journal_root,
+            update_journal_root,
+            remove_journal_root,
+            set_filename_pattern,
+            set_date_field,
+            set_journal_globs,
+            set_inline_fields,
             add_metric,
             delete_metric,
             udpate_metric,
+            set_metric_checkbox_label,
+            set_metric_aliases,
+            set_metric_aggregation,
+            set_metric_unit,
+            set_derived_metric,
+            set_normalize_metric_keys,
+            set_source_formats,
+            set_watch_debounce,
+            set_reconcile_interval,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -11,6 +11,9 @@ import { listen } from "@tauri-apps/api/event";
 
 type SyncProgress = {
 	syncProgress: number;
+	processed: number;
+	failed: number;
+	remaining: number;
 };
 
 export default function SyncProgress({
@@ -21,6 +24,7 @@ export default function SyncProgress({
 	onOpenChange: (open: boolean) => void;
 }) {
 	const [progress, setProgress] = useState(0);
+	const [failed, setFailed] = useState(0);
 
 	useEffect(() => {
 		if (!open) return;
@@ -29,6 +33,7 @@ export default function SyncProgress({
 			const newProgress = event.payload.syncProgress;
 			console.log("newProgress", newProgress);
 			setProgress(newProgress);
+			setFailed(event.payload.failed);
 		});
 
 		return () => {
@@ -39,6 +44,7 @@ export default function SyncProgress({
 	useEffect(() => {
 		if (!open) {
 			setProgress(0);
+			setFailed(0);
 		}
 	}, [open]);
 
@@ -52,7 +58,10 @@ export default function SyncProgress({
 					<AlertDialogDescription asChild>
 						<span className="flex items-center mt-2">
 							<Loader2 className="mr-2 h-4 w-4 animate-spin" />
-							<span>{progress}% Complete</span>
+							<span>
+								{progress}% Complete
+								{failed > 0 && ` (${failed} failed)`}
+							</span>
 						</span>
 					</AlertDialogDescription>
 				</AlertDialogHeader>